@@ -1,7 +1,39 @@
 use std::env;
+use std::path::Path;
+use std::process::Command;
 
 fn main() {
     println!("cargo:rustc-env=HOST={}", env::var("TARGET").unwrap());
     // Prevents cargo from scanning the whole directory for changes.
     println!("cargo:rerun-if-changed=build.rs");
+
+    let version = env::var("CARGO_PKG_VERSION").unwrap();
+    let version = match git_commit() {
+        Some(commit) => format!("{version} ({commit})"),
+        None => version,
+    };
+    println!("cargo:rustc-env=CARGO_BISECT_VERSION={version}");
+}
+
+/// The short hash and date of the commit being built, like `rustc --version`
+/// shows them, when building from a git checkout of this repository rather
+/// than from a published package.
+fn git_commit() -> Option<String> {
+    let git_dir = Path::new(&env::var_os("CARGO_MANIFEST_DIR")?).join(".git");
+    if !git_dir.exists() {
+        return None;
+    }
+    // Rebuild with the new hash after a commit or a checkout.
+    println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+    println!("cargo:rerun-if-changed={}", git_dir.join("refs").display());
+    let output = Command::new("git")
+        .args(["log", "-1", "--abbrev=9", "--format=%h %cs"])
+        .current_dir(git_dir.parent()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let commit = String::from_utf8(output.stdout).ok()?;
+    Some(commit.trim().to_string()).filter(|commit| !commit.is_empty())
 }
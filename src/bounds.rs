@@ -1,16 +1,19 @@
 //! Definitions of bisection bounds.
 
 use crate::toolchains::{
-    download_progress, parse_to_naive_date, Toolchain, NIGHTLY_SERVER, YYYY_MM_DD,
+    ci_artifacts_exist, display_sha, download_progress, nightly_manifest_commit,
+    parse_to_naive_date, DownloadError, Toolchain, NIGHTLY_SERVER, YYYY_MM_DD,
 };
-use crate::GitDate;
-use crate::Opts;
-use crate::{today, EPOCH_COMMIT};
+use crate::{today, CI_RETENTION_DAYS, EPOCH_COMMIT};
+use crate::{BisectBy, GitDate, Opts};
 use anyhow::bail;
 use chrono::NaiveDate;
+use colored::Colorize;
+use regex::Regex;
 use reqwest::blocking::Client;
 use std::io::Read;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
 /// A bisection boundary.
 #[derive(Clone, Debug)]
@@ -40,14 +43,23 @@ impl Bound {
                 let url =
                     format!("{NIGHTLY_SERVER}/{date_str}/channel-rust-nightly-git-commit-hash.txt");
 
-                eprintln!("fetching {url}");
+                network_log!("fetching {url}");
                 let client = Client::new();
                 let name = format!("nightly manifest {date_str}");
-                let mut response = download_progress(&client, &name, &url)?;
-                let mut commit = String::new();
-                response.read_to_string(&mut commit)?;
+                let commit = match download_progress(&client, &name, &url) {
+                    Ok(mut response) => {
+                        let mut commit = String::new();
+                        response.read_to_string(&mut commit)?;
+                        commit
+                    }
+                    Err(DownloadError::NotFound(_)) => {
+                        network_log!("{url} not found, reading the channel manifest instead");
+                        nightly_manifest_commit(&client, *date)?
+                    }
+                    Err(err) => return Err(err.into()),
+                };
 
-                eprintln!("converted {date_str} to {commit}");
+                network_log!("converted {date_str} to {}", display_sha(&commit));
 
                 Ok(commit)
             }
@@ -70,31 +82,61 @@ pub enum Bounds {
 
 impl Bounds {
     pub fn from_args(args: &Opts) -> anyhow::Result<Bounds> {
+        if args.by_commit && args.bisect_by == BisectBy::Date {
+            bail!("--by-commit cannot be combined with --bisect-by=date");
+        }
+        let by_commit = args.by_commit || args.bisect_by == BisectBy::Commit;
         let (start, end) = translate_tags(&args)?;
+        if let Some(count) = args.last {
+            if args.bisect_by == BisectBy::Date {
+                bail!("--last cannot be combined with --bisect-by=date");
+            }
+            return last_commits(args, count, end);
+        }
+        let (start, end) = match map_bounds(args, start.as_ref(), end.as_ref()) {
+            MapBounds::Keep => (start, end),
+            MapBounds::CommitsToDates => (
+                commit_to_date(args, "start", start)?,
+                commit_to_date(args, "end", end)?,
+            ),
+            MapBounds::DatesToCommits => (
+                date_to_commit(args, "start", start)?,
+                date_to_commit(args, "end", end)?,
+            ),
+        };
         let today = today();
-        let check_in_future = |which, date: &NaiveDate| -> anyhow::Result<()> {
-            if date > &today {
-                bail!(
-                    "{which} date should be on or before current date, \
-                     got {which} date request: {date} and current date is {today}"
-                );
+        // Returns the date to use for a date bound, after checking it is not in
+        // the future. A bound of tomorrow (UTC) is moved back to the latest nightly.
+        let check_in_future = |which, date: NaiveDate| -> anyhow::Result<NaiveDate> {
+            check_not_in_future(which, date, today)?;
+            if date > today {
+                let latest = find_latest_nightly()?;
+                if latest < date {
+                    eprintln!("{which} date {date} is after today (UTC), using {latest} instead");
+                    return Ok(latest);
+                }
             }
-            Ok(())
+            Ok(date)
         };
         let bounds = match (start, end) {
             // Neither --start or --end specified.
-            (None, None) => Bounds::SearchNightlyBackwards {
-                end: installed_nightly_or_latest()?,
-            },
+            (None, None) => {
+                if args.bisect_by == BisectBy::Commit {
+                    bail!("--bisect-by=commit requires --start or --end to be specified");
+                }
+                Bounds::SearchNightlyBackwards {
+                    end: installed_nightly_or_latest()?,
+                }
+            }
 
             // --start or --end is a commit
             (Some(Bound::Commit(start)), Some(Bound::Commit(end))) => {
                 Bounds::Commits { start, end }
             }
-            (Some(Bound::Commit(start)), None) => Bounds::Commits {
-                start,
-                end: args.access.repo().commit("origin/master")?.sha,
-            },
+            (Some(Bound::Commit(start)), None) => {
+                let end = latest_master_with_artifacts(args, &start)?;
+                Bounds::Commits { start, end }
+            }
             (None, Some(Bound::Commit(end))) => Bounds::Commits {
                 start: EPOCH_COMMIT.to_string(),
                 end,
@@ -102,39 +144,36 @@ impl Bounds {
 
             // --start or --end is a date
             (Some(Bound::Date(start)), Some(Bound::Date(end))) => {
-                check_in_future("start", &start)?;
-                check_in_future("end", &end)?;
+                let start = check_in_future("start", start)?;
+                let end = exclusive_end(args, check_in_future("end", end)?);
                 Bounds::Dates { start, end }
             }
             (Some(Bound::Date(start)), None) => {
-                check_in_future("start", &start)?;
+                let start = check_in_future("start", start)?;
                 Bounds::Dates {
                     start,
                     end: find_latest_nightly()?,
                 }
             }
             (None, Some(Bound::Date(end))) => {
-                check_in_future("end", &end)?;
-                if args.by_commit {
+                let end = exclusive_end(args, check_in_future("end", end)?);
+                if by_commit {
                     bail!("--by-commit with an end date requires --start to be specified");
                 }
                 Bounds::SearchNightlyBackwards { end }
             }
 
-            // Mixed not supported.
             (Some(Bound::Commit(_)), Some(Bound::Date(_)))
-            | (Some(Bound::Date(_)), Some(Bound::Commit(_))) => bail!(
-                "cannot take different types of bounds for start/end, \
-                 got start: {:?} and end {:?}",
-                args.start,
-                args.end
-            ),
+            | (Some(Bound::Date(_)), Some(Bound::Commit(_))) => {
+                unreachable!("mixed bounds are mapped by `map_bounds`")
+            }
         };
         if let Bounds::Dates { start, end } = &bounds {
             if end < start {
                 bail!("end should be after start, got start: {start} and end {end}");
             }
-            if args.by_commit {
+            if by_commit {
+                warn_if_ci_expired(*end, today);
                 eprintln!("finding commit range that corresponds to dates specified");
                 let bounds = Bounds::Commits {
                     start: date_to_sha(&start)?,
@@ -147,12 +186,137 @@ impl Bounds {
     }
 }
 
+/// Checks that a `--known-good-date`, if any, can start the nightly range
+/// `bounds`: it must not be before `--start`, and must be before the end.
+pub(crate) fn check_known_good_date(date: Option<GitDate>, bounds: &Bounds) -> anyhow::Result<()> {
+    let Some(date) = date else {
+        return Ok(());
+    };
+    let (start, end) = match *bounds {
+        Bounds::Commits { .. } => {
+            bail!("--known-good-date only applies to nightlies, but the bounds are commits")
+        }
+        Bounds::Dates { start, end } => (Some(start), end),
+        Bounds::SearchNightlyBackwards { end } => (None, end),
+    };
+    if date >= end {
+        bail!("--known-good-date {date} must be before the end of the range, {end}");
+    }
+    if let Some(start) = start.filter(|start| date < *start) {
+        bail!("--known-good-date {date} must not be before the start of the range, {start}");
+    }
+    Ok(())
+}
+
+/// The last nightly to search up to for the `--end` date `end`, which is the
+/// nightly before it with `--end-exclusive`.
+fn exclusive_end(args: &Opts, end: GitDate) -> GitDate {
+    if !args.end_exclusive {
+        return end;
+    }
+    let before = end.pred_opt().unwrap();
+    eprintln!("--end-exclusive: searching up to {before}, the nightly before {end}");
+    before
+}
+
+/// Warns when a commit bisection ending at `end` can't work because the CI
+/// artifacts of the whole range have already been deleted. Commit bounds are
+/// checked once their commits have been looked up for the bisection.
+pub(crate) fn warn_if_ci_expired(end: GitDate, today: GitDate) {
+    if let Some(msg) = ci_expired_message(end, today) {
+        eprintln!("{} {msg}", "WARNING:".yellow().bold());
+    }
+}
+
+fn ci_expired_message(end: GitDate, today: GitDate) -> Option<String> {
+    let age = (today - end).num_days();
+    (age >= CI_RETENTION_DAYS).then(|| {
+        format!(
+            "the end of the range ({end}) is {age} days old, but CI artifacts are only kept \
+             for {CI_RETENTION_DAYS} days, so none are left to bisect. \
+             Nightlies are kept much longer: use date bounds without --by-commit \
+             to bisect nightlies instead."
+        )
+    })
+}
+
+/// Whether `commit` looks like a release tag such as `1.62.0` or `1.62`, as
+/// opposed to a commit SHA or some other ref.
+fn is_release_tag(commit: &str) -> bool {
+    static RELEASE_TAG: OnceLock<Regex> = OnceLock::new();
+    RELEASE_TAG
+        .get_or_init(|| Regex::new(r"^\d+\.\d+(\.\d+)?$").unwrap())
+        .is_match(commit)
+}
+
+/// The scheduled release date of the stable `1.N.0` release that `tag`
+/// belongs to. Stable releases ship every six weeks on a Thursday, starting
+/// with 1.1.0 on 2015-06-25.
+fn stable_release_date(tag: &str) -> Option<GitDate> {
+    let mut parts = tag.split('.');
+    let (major, minor) = (parts.next()?, parts.next()?);
+    if major != "1" {
+        return None;
+    }
+    let minor: u32 = minor.parse().ok()?;
+    if minor == 0 {
+        return NaiveDate::from_ymd_opt(2015, 5, 15);
+    }
+    let release_zero = NaiveDate::from_ymd_opt(2015, 5, 14)?;
+    release_zero.checked_add_signed(chrono::Duration::weeks(6 * i64::from(minor)))
+}
+
+/// The minor version of the nightlies of `date`, following the schedule of
+/// [`stable_release_date`]: master becomes `1.N.0` when `1.(N-2).0` is
+/// released, around when `1.(N-1).0` branches off to beta.
+pub(crate) fn nightly_minor_version(date: GitDate) -> u32 {
+    let release_zero = NaiveDate::from_ymd_opt(2015, 5, 14).unwrap();
+    let releases = (date - release_zero).num_weeks().max(0) / 6;
+    u32::try_from(releases).unwrap_or(u32::MAX - 2) + 2
+}
+
+/// Prints the releases and the nightly window that a bisection between two
+/// release tags covers.
+fn report_tag_range(start_tag: &str, start: GitDate, end_tag: &str, end: GitDate) {
+    eprintln!("bisecting between releases {start_tag} and {end_tag}");
+    for (tag, tagged) in [(start_tag, start), (end_tag, end)] {
+        let released = stable_release_date(tag).map_or_else(
+            || "unknown release date".to_string(),
+            |date| format!("released {}", date.format(YYYY_MM_DD)),
+        );
+        eprintln!(
+            "  {tag}: {released}, tagged commit dated {}",
+            tagged.format(YYYY_MM_DD)
+        );
+    }
+    eprintln!(
+        "  nightly window: {} to {}",
+        start.format(YYYY_MM_DD),
+        end.format(YYYY_MM_DD)
+    );
+}
+
+/// Checks that a date bound is not in the future.
+///
+/// `today` is the current UTC date. Dates up to one day after it are accepted,
+/// since the local date in timezones east of UTC can already be a day ahead.
+fn check_not_in_future(which: &str, date: NaiveDate, today: NaiveDate) -> anyhow::Result<()> {
+    let latest = today + chrono::Duration::days(1);
+    if date > latest {
+        bail!(
+            "{which} date should be on or before current date, \
+             got {which} date request: {date} and current date is {today} (UTC)"
+        );
+    }
+    Ok(())
+}
+
 /// Translates a tag-like bound (such as `1.62.0`) to a `Bound::Date` so that
 /// bisecting works for versions older than 167 days.
 fn translate_tags(args: &Opts) -> anyhow::Result<(Option<Bound>, Option<Bound>)> {
     let is_tag = |bound: &Option<Bound>| -> bool {
         match bound {
-            Some(Bound::Commit(commit)) => commit.contains('.'),
+            Some(Bound::Commit(commit)) => is_release_tag(commit),
             None | Some(Bound::Date(_)) => false,
         }
     };
@@ -180,7 +344,190 @@ fn translate_tags(args: &Opts) -> anyhow::Result<(Option<Bound>, Option<Bound>)>
         }
         Ok(bound.clone())
     };
-    Ok((fixup("start", &args.start)?, fixup("end", &args.end)?))
+    let (start, end) = (fixup("start", &args.start)?, fixup("end", &args.end)?);
+    if let (
+        Some(Bound::Commit(start_tag)),
+        Some(Bound::Commit(end_tag)),
+        Some(Bound::Date(start)),
+        Some(Bound::Date(end)),
+    ) = (&args.start, &args.end, &start, &end)
+    {
+        report_tag_range(start_tag, *start, end_tag, *end);
+    }
+    Ok((start, end))
+}
+
+/// How many commits to walk back from the tip of master looking for one whose
+/// CI artifacts have been uploaded.
+const MAX_COMMITS_WITHOUT_ARTIFACTS: usize = 10;
+
+/// The bounds for `--last`: the `count` most recent bors merges ending with
+/// `end`, or with the latest commit on master that has CI artifacts.
+fn last_commits(args: &Opts, count: usize, end: Option<Bound>) -> anyhow::Result<Bounds> {
+    if count < 2 {
+        bail!("--last needs at least 2 commits to bisect, got {count}");
+    }
+    let end = match end {
+        Some(Bound::Commit(end)) => Some(end),
+        Some(Bound::Date(date)) => bail!("--last requires --end to be a commit, got {date}"),
+        None => None,
+    };
+    let access = args.access.repo();
+    let commits = access.recent_commits(end.as_deref().unwrap_or("origin/master"), count)?;
+    let Some(first) = commits.first() else {
+        bail!("found no bors merges to bisect");
+    };
+    if commits.len() < count {
+        eprintln!(
+            "only found {} bors merges, bisecting all of them",
+            commits.len()
+        );
+    }
+    let start = first.sha.clone();
+    let end = match end {
+        Some(end) => end,
+        None => latest_master_with_artifacts(args, &start)?,
+    };
+    eprintln!(
+        "bisecting the last {} bors merges, from {} to {}",
+        commits.len(),
+        display_sha(&start),
+        display_sha(&end)
+    );
+    Ok(Bounds::Commits { start, end })
+}
+
+/// Returns the SHA of the most recent master commit with CI artifacts.
+///
+/// The artifacts of the tip of master are usually still being uploaded (or
+/// the commit is not even built yet), so using it blindly as the end of the
+/// range would make the end-boundary install fail.
+fn latest_master_with_artifacts(args: &Opts, start: &str) -> anyhow::Result<String> {
+    let access = args.access.repo();
+    let client = Client::new();
+    let tip = access.commit("origin/master")?;
+    if ci_artifacts_exist(&client, args, &tip.sha)? {
+        return Ok(tip.sha);
+    }
+    eprintln!(
+        "the tip of master ({}) has no CI artifacts yet, looking for an older commit",
+        tip.sha
+    );
+    let commits = access.commits(start, &tip.sha)?;
+    // Skip the tip, which we just checked, and never go back as far as `start`.
+    for commit in commits
+        .iter()
+        .skip(1)
+        .rev()
+        .skip(1)
+        .take(MAX_COMMITS_WITHOUT_ARTIFACTS)
+    {
+        if ci_artifacts_exist(&client, args, &commit.sha)? {
+            eprintln!("using {} as the end of the range", display_sha(&commit.sha));
+            return Ok(commit.sha.clone());
+        }
+        eprintln!("{} has no CI artifacts yet", display_sha(&commit.sha));
+    }
+    bail!(
+        "could not find CI artifacts for any of the last {} commits on master; \
+         please specify --end explicitly",
+        MAX_COMMITS_WITHOUT_ARTIFACTS + 1
+    )
+}
+
+/// How [`Bounds::from_args`] maps the bounds before using them.
+#[derive(Debug, PartialEq, Eq)]
+enum MapBounds {
+    Keep,
+    CommitsToDates,
+    DatesToCommits,
+}
+
+/// Commit bounds are mapped to nightly dates with `--bisect-by=date`. A
+/// commit bound paired with a date bound, such as the `commit-hash` printed by
+/// `rustc -Vv` of a nightly, is mapped to its nightly too, unless bisecting
+/// by commit, which maps the date bound to the commit of its nightly instead.
+fn map_bounds(args: &Opts, start: Option<&Bound>, end: Option<&Bound>) -> MapBounds {
+    let mixed = matches!(
+        (start, end),
+        (Some(Bound::Commit(_)), Some(Bound::Date(_)))
+            | (Some(Bound::Date(_)), Some(Bound::Commit(_)))
+    );
+    if args.bisect_by == BisectBy::Date {
+        MapBounds::CommitsToDates
+    } else if !mixed {
+        MapBounds::Keep
+    } else if args.by_commit || args.bisect_by == BisectBy::Commit {
+        MapBounds::DatesToCommits
+    } else {
+        MapBounds::CommitsToDates
+    }
+}
+
+/// Maps a date bound to the commit its nightly was built from, for a date
+/// bound paired with a commit bound when bisecting by commit.
+fn date_to_commit(args: &Opts, which: &str, bound: Option<Bound>) -> anyhow::Result<Option<Bound>> {
+    match bound {
+        Some(Bound::Date(date)) => {
+            let date = if which == "end" {
+                exclusive_end(args, date)
+            } else {
+                date
+            };
+            let sha = date_to_sha(&date)?.trim().to_string();
+            eprintln!(
+                "translating --{which}={} to {}, the commit of that nightly",
+                date.format(YYYY_MM_DD),
+                display_sha(&sha)
+            );
+            Ok(Some(Bound::Commit(sha)))
+        }
+        bound => Ok(bound),
+    }
+}
+
+/// Maps a commit bound to the date of a nightly, for `--bisect-by=date` and
+/// for a commit bound paired with a date bound.
+fn commit_to_date(args: &Opts, which: &str, bound: Option<Bound>) -> anyhow::Result<Option<Bound>> {
+    match bound {
+        Some(Bound::Commit(commit)) => {
+            let commit = args.access.repo().commit(&commit)?;
+            let next_day = commit.date.succ_opt().unwrap();
+            // The commit `rustc -Vv` reports for a nightly is the one that
+            // nightly was built from.
+            let is_nightly = date_to_sha(&next_day).is_ok_and(|sha| sha.trim() == commit.sha);
+            let date = nightly_date_for_commit(which == "end", commit.date, is_nightly);
+            eprintln!(
+                "translating --{which}={} to {}{}",
+                display_sha(&commit.sha),
+                date.format(YYYY_MM_DD),
+                if is_nightly {
+                    ", the nightly built from it"
+                } else {
+                    ""
+                }
+            );
+            Ok(Some(Bound::Date(date)))
+        }
+        bound => Ok(bound),
+    }
+}
+
+/// Returns the date of the nightly to use for a commit bound merged on
+/// `commit_date`.
+///
+/// The nightly of a date is built from the last commit merged before that
+/// date started (UTC), so the nightly of the next day is the first one that
+/// includes the commit, and is built from it when `is_nightly` is set. The
+/// end bound, which has the regression, uses that nightly. The start bound
+/// uses the nightly of the commit date, which cannot include commits merged
+/// after it, unless it was built from the commit exactly.
+fn nightly_date_for_commit(is_end: bool, commit_date: GitDate, is_nightly: bool) -> GitDate {
+    if is_end || is_nightly {
+        commit_date.succ_opt().unwrap()
+    } else {
+        commit_date
+    }
 }
 
 /// Returns the commit SHA of the nightly associated with the given date.
@@ -188,14 +535,14 @@ fn date_to_sha(date: &NaiveDate) -> anyhow::Result<String> {
     let date_str = date.format(YYYY_MM_DD);
     let url = format!("{NIGHTLY_SERVER}/{date_str}/channel-rust-nightly-git-commit-hash.txt");
 
-    eprintln!("fetching {url}");
+    network_log!("fetching {url}");
     let client = Client::new();
     let name = format!("nightly manifest {date_str}");
     let mut response = download_progress(&client, &name, &url)?;
     let mut commit = String::new();
     response.read_to_string(&mut commit)?;
 
-    eprintln!("converted {date_str} to {commit}");
+    network_log!("converted {date_str} to {}", display_sha(&commit));
 
     Ok(commit)
 }
@@ -213,12 +560,159 @@ fn installed_nightly_or_latest() -> anyhow::Result<GitDate> {
 /// Returns the date of the latest nightly (fetched from the network).
 fn find_latest_nightly() -> anyhow::Result<GitDate> {
     let url = format!("{NIGHTLY_SERVER}/channel-rust-nightly-date.txt");
-    eprintln!("fetching {url}");
+    network_log!("fetching {url}");
     let client = Client::new();
     let mut response = download_progress(&client, "nightly date", &url)?;
     let mut body = String::new();
     response.read_to_string(&mut body)?;
     let date = NaiveDate::parse_from_str(&body, "%Y-%m-%d")?;
-    eprintln!("determined the latest nightly is {date}");
+    network_log!("determined the latest nightly is {date}");
     Ok(date)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_map_bounds() {
+        let map = |args: &[&str]| {
+            let args = Opts::try_parse_from(["bisect"].iter().chain(args)).unwrap();
+            map_bounds(&args, args.start.as_ref(), args.end.as_ref())
+        };
+        let sha = "6cbf0926d54c80ea6d15df333be9281f65bbeb36";
+        let commit_date = ["--start", sha, "--end", "2024-03-05"];
+        let date_commit = ["--start", "2024-03-05", "--end", sha];
+        assert_eq!(map(&commit_date), MapBounds::CommitsToDates);
+        assert_eq!(map(&date_commit), MapBounds::CommitsToDates);
+        assert_eq!(
+            map(&[&commit_date[..], &["--bisect-by=commit"]].concat()),
+            MapBounds::DatesToCommits
+        );
+        assert_eq!(
+            map(&[&date_commit[..], &["--by-commit"]].concat()),
+            MapBounds::DatesToCommits
+        );
+        assert_eq!(
+            map(&["--start", sha, "--end", sha, "--bisect-by=date"]),
+            MapBounds::CommitsToDates
+        );
+        assert_eq!(map(&["--start", sha, "--end", sha]), MapBounds::Keep);
+        assert_eq!(
+            map(&["--start", "2024-03-01", "--end", "2024-03-05"]),
+            MapBounds::Keep
+        );
+    }
+
+    #[test]
+    fn test_ci_expired_message() {
+        let today = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let recent = today - chrono::Duration::days(CI_RETENTION_DAYS - 1);
+        assert!(ci_expired_message(recent, today).is_none());
+        let old = today - chrono::Duration::days(CI_RETENTION_DAYS);
+        let msg = ci_expired_message(old, today).unwrap();
+        assert!(msg.contains(&format!("is {CI_RETENTION_DAYS} days old")));
+    }
+
+    #[test]
+    fn test_exclusive_end() {
+        let args = |extra: &[&str]| {
+            Opts::try_parse_from(
+                ["cargo-bisect-rustc", "--end=2023-03-01"]
+                    .iter()
+                    .chain(extra),
+            )
+            .unwrap()
+        };
+        let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let before = NaiveDate::from_ymd_opt(2023, 2, 28).unwrap();
+        assert_eq!(exclusive_end(&args(&[]), end), end);
+        assert_eq!(exclusive_end(&args(&["--end-exclusive"]), end), before);
+        assert_eq!(
+            exclusive_end(&args(&["--end-exclusive", "--end-inclusive"]), end),
+            end
+        );
+        assert_eq!(
+            exclusive_end(&args(&["--end-inclusive", "--end-exclusive"]), end),
+            before
+        );
+        assert!(Opts::try_parse_from(["cargo-bisect-rustc", "--end-exclusive"]).is_err());
+    }
+
+    #[test]
+    fn test_nightly_date_for_commit() {
+        let date = NaiveDate::from_ymd_opt(2023, 3, 10).unwrap();
+        let next_day = NaiveDate::from_ymd_opt(2023, 3, 11).unwrap();
+        assert_eq!(nightly_date_for_commit(false, date, false), date);
+        assert_eq!(nightly_date_for_commit(true, date, false), next_day);
+        // The commit-hash of a nightly maps to that nightly for both bounds.
+        assert_eq!(nightly_date_for_commit(false, date, true), next_day);
+        assert_eq!(nightly_date_for_commit(true, date, true), next_day);
+    }
+
+    #[test]
+    fn test_check_not_in_future() {
+        let today = NaiveDate::from_ymd_opt(2023, 3, 10).unwrap();
+        assert!(check_not_in_future("end", today, today).is_ok());
+        assert!(check_not_in_future("end", today.pred_opt().unwrap(), today).is_ok());
+        assert!(check_not_in_future("end", today.succ_opt().unwrap(), today).is_ok());
+        let err = check_not_in_future("end", NaiveDate::from_ymd_opt(2023, 3, 12).unwrap(), today)
+            .unwrap_err();
+        assert!(err.to_string().contains("current date is 2023-03-10 (UTC)"));
+    }
+
+    #[test]
+    fn test_is_release_tag() {
+        assert!(is_release_tag("1.62.0"));
+        assert!(is_release_tag("1.62"));
+        assert!(!is_release_tag("some.branch"));
+        assert!(!is_release_tag("1.62.0-beta.1"));
+        assert!(!is_release_tag("6a94f9c8fd0c5e5b1f7b8ea0b4b7c1f4e2b6c7d9"));
+    }
+
+    #[test]
+    fn test_stable_release_date() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(stable_release_date("1.0.0"), date(2015, 5, 15));
+        assert_eq!(stable_release_date("1.1.0"), date(2015, 6, 25));
+        assert_eq!(stable_release_date("1.70.0"), date(2023, 6, 1));
+        assert_eq!(stable_release_date("1.71"), date(2023, 7, 13));
+        assert_eq!(stable_release_date("2.0.0"), None);
+    }
+
+    #[test]
+    fn test_check_known_good_date() {
+        let date = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+        let dates = Bounds::Dates {
+            start: date(5),
+            end: date(20),
+        };
+        assert!(check_known_good_date(Some(date(5)), &dates).is_ok());
+        assert!(check_known_good_date(Some(date(19)), &dates).is_ok());
+        assert!(check_known_good_date(None, &dates).is_ok());
+        assert!(check_known_good_date(Some(date(4)), &dates).is_err());
+        assert!(check_known_good_date(Some(date(20)), &dates).is_err());
+
+        let backwards = Bounds::SearchNightlyBackwards { end: date(20) };
+        assert!(check_known_good_date(Some(date(1)), &backwards).is_ok());
+        assert!(check_known_good_date(Some(date(21)), &backwards).is_err());
+
+        let commits = Bounds::Commits {
+            start: "a".to_string(),
+            end: "b".to_string(),
+        };
+        assert!(check_known_good_date(Some(date(1)), &commits).is_err());
+    }
+
+    #[test]
+    fn test_nightly_minor_version() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        // 1.69.0 was released on 2023-04-20 and 1.70.0 on 2023-06-01.
+        assert_eq!(nightly_minor_version(date(2023, 4, 19)), 70);
+        assert_eq!(nightly_minor_version(date(2023, 4, 20)), 71);
+        assert_eq!(nightly_minor_version(date(2023, 5, 31)), 71);
+        assert_eq!(nightly_minor_version(date(2023, 6, 1)), 72);
+        assert_eq!(nightly_minor_version(date(2014, 12, 1)), 2);
+    }
+}
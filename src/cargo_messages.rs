@@ -0,0 +1,250 @@
+//! Reading cargo's `--message-format=json` output, to judge the outcome of a
+//! test by the diagnostics of a single crate, or by whether a lint fired.
+
+use std::ffi::OsString;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum CargoMessage {
+    CompilerMessage {
+        target: Target,
+        message: Diagnostic,
+    },
+    CompilerArtifact {
+        target: Target,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    level: String,
+    code: Option<DiagnosticCode>,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticCode {
+    code: String,
+}
+
+/// A lint to look for with `--regress-lint`, at a given level or at any.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LintMatch {
+    pub(crate) code: String,
+    /// The diagnostic level, `warning` or `error`.
+    pub(crate) level: Option<&'static str>,
+}
+
+impl LintMatch {
+    /// Parses `LINT` or `LINT:LEVEL`, where the level is `warn` or `deny`, or
+    /// the `warning` or `error` that the diagnostic is reported with.
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        let (code, level) = match s.rsplit_once(':') {
+            // `clippy::lint` has colons of its own.
+            Some((code, level)) if !code.ends_with(':') && !level.is_empty() => {
+                let level = match level {
+                    "warn" | "warning" => "warning",
+                    "deny" | "forbid" | "error" => "error",
+                    _ => {
+                        return Err(format!(
+                            "unknown lint level `{level}`, expected `warn` or `deny`"
+                        ))
+                    }
+                };
+                (code, Some(level))
+            }
+            _ => (s, None),
+        };
+        if code.is_empty() {
+            return Err("the lint name is empty".to_string());
+        }
+        Ok(LintMatch {
+            code: code.to_string(),
+            level,
+        })
+    }
+}
+
+impl std::fmt::Display for LintMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.level {
+            Some(level) => write!(f, "`{}` at level `{level}`", self.code),
+            None => write!(f, "`{}`", self.code),
+        }
+    }
+}
+
+/// What cargo's output says about one crate.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct CrateReport {
+    /// Whether cargo finished building a target of the crate.
+    pub(crate) built: bool,
+    /// The number of errors reported for the crate.
+    pub(crate) errors: usize,
+    /// Whether the compiler reported an internal compiler error for the crate.
+    pub(crate) ice: bool,
+}
+
+/// Summarizes the messages about the crate `name` in cargo's JSON output.
+/// Lines that are not JSON messages, such as the output of build scripts or
+/// test binaries, are ignored.
+pub(crate) fn crate_report(stdout: &str, name: &str) -> CrateReport {
+    let name = normalize(name);
+    let mut report = CrateReport::default();
+    for line in stdout.lines().filter(|line| line.starts_with('{')) {
+        match serde_json::from_str(line) {
+            Ok(CargoMessage::CompilerMessage { target, message })
+                if normalize(&target.name) == name =>
+            {
+                if message.level == "error: internal compiler error" {
+                    report.ice = true;
+                    report.errors += 1;
+                } else if message.level == "error" {
+                    report.errors += 1;
+                }
+            }
+            Ok(CargoMessage::CompilerArtifact { target }) if normalize(&target.name) == name => {
+                report.built = true;
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+/// Whether cargo's JSON output reports the lint, for the crate `name` if
+/// given, or for any crate otherwise.
+pub(crate) fn lint_fired(stdout: &str, lint: &LintMatch, name: Option<&str>) -> bool {
+    let name = name.map(normalize);
+    stdout
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .any(|line| match serde_json::from_str(line) {
+            Ok(CargoMessage::CompilerMessage { target, message }) => {
+                name.as_ref()
+                    .is_none_or(|name| normalize(&target.name) == *name)
+                    && message.code.is_some_and(|code| code.code == lint.code)
+                    && lint.level.is_none_or(|level| message.level == level)
+            }
+            _ => false,
+        })
+}
+
+/// Crate names may be written with `-` or `_`.
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Adds `--message-format=json` to the arguments of a cargo command, before
+/// any `--` that starts the arguments passed through to another program.
+pub(crate) fn with_json_message_format(args: &[OsString]) -> Vec<OsString> {
+    let split = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let mut args = args.to_vec();
+    args.insert(split, "--message-format=json".into());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crate_report() {
+        let stdout = r#"{"reason":"compiler-artifact","target":{"name":"dep","kind":["lib"]},"fresh":false}
+{"reason":"compiler-message","target":{"name":"my_crate"},"message":{"level":"warning","message":"unused variable"}}
+{"reason":"compiler-message","target":{"name":"other"},"message":{"level":"error","message":"mismatched types"}}
+{"reason":"compiler-message","target":{"name":"my_crate"},"message":{"level":"error","message":"mismatched types"}}
+{"reason":"build-script-executed","package_id":"dep 0.1.0"}
+not json
+{"reason":"build-finished","success":false}
+"#;
+        assert_eq!(
+            crate_report(stdout, "my-crate"),
+            CrateReport {
+                built: false,
+                errors: 1,
+                ice: false,
+            }
+        );
+        assert_eq!(
+            crate_report(stdout, "dep"),
+            CrateReport {
+                built: true,
+                errors: 0,
+                ice: false,
+            }
+        );
+
+        let ice = r#"{"reason":"compiler-message","target":{"name":"my_crate"},"message":{"level":"error: internal compiler error","message":"oops"}}"#;
+        assert!(crate_report(ice, "my_crate").ice);
+    }
+
+    #[test]
+    fn test_lint_fired() {
+        let stdout = r#"{"reason":"compiler-message","target":{"name":"my_crate"},"message":{"level":"warning","code":{"code":"unused_variables","explanation":null},"message":"unused variable: `x`"}}
+{"reason":"compiler-message","target":{"name":"other"},"message":{"level":"error","code":{"code":"dead_code","explanation":null},"message":"function `f` is never used"}}
+{"reason":"compiler-message","target":{"name":"my_crate"},"message":{"level":"error","code":null,"message":"aborting due to 1 previous error"}}
+"#;
+        let lint = |s| LintMatch::parse(s).unwrap();
+        assert!(lint_fired(stdout, &lint("unused_variables"), None));
+        assert!(lint_fired(
+            stdout,
+            &lint("unused_variables:warn"),
+            Some("my-crate")
+        ));
+        assert!(!lint_fired(stdout, &lint("unused_variables:deny"), None));
+        assert!(lint_fired(stdout, &lint("dead_code:error"), None));
+        assert!(!lint_fired(stdout, &lint("dead_code"), Some("my_crate")));
+        assert!(!lint_fired(stdout, &lint("unused_mut"), None));
+    }
+
+    #[test]
+    fn test_lint_match_parse() {
+        assert_eq!(
+            LintMatch::parse("unused_variables:deny"),
+            Ok(LintMatch {
+                code: "unused_variables".to_string(),
+                level: Some("error"),
+            })
+        );
+        assert_eq!(
+            LintMatch::parse("clippy::needless_return"),
+            Ok(LintMatch {
+                code: "clippy::needless_return".to_string(),
+                level: None,
+            })
+        );
+        assert_eq!(
+            LintMatch::parse("clippy::needless_return:warn")
+                .unwrap()
+                .level,
+            Some("warning")
+        );
+        assert!(LintMatch::parse("unused_variables:loud").is_err());
+        assert!(LintMatch::parse(":deny").is_err());
+    }
+
+    #[test]
+    fn test_with_json_message_format() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            with_json_message_format(&args(&["build"])),
+            ["build", "--message-format=json"]
+        );
+        assert_eq!(
+            with_json_message_format(&args(&["test", "--", "--nocapture"])),
+            ["test", "--message-format=json", "--", "--nocapture"]
+        );
+    }
+}
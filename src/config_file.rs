@@ -0,0 +1,179 @@
+//! Loading default options from a TOML file given with `--config`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+
+use crate::value_parsers::{validate_target, validate_triple};
+use crate::{Access, Opts, RegressOn};
+
+/// The options that can be set in a configuration file.
+///
+/// Keys use the same names as the command-line flags, for example:
+///
+/// ```toml
+/// host = "x86_64-unknown-linux-gnu"
+/// access = "checkout"
+/// preserve = true
+/// components = ["rust-src"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConfigFile {
+    regress: Option<RegressOn>,
+    alt: Option<bool>,
+    host: Option<String>,
+    target: Option<String>,
+    preserve: Option<bool>,
+    preserve_target: Option<bool>,
+    with_src: Option<bool>,
+    with_dev: Option<bool>,
+    components: Option<Vec<String>>,
+    test_dir: Option<PathBuf>,
+    prompt: Option<bool>,
+    timeout: Option<usize>,
+    by_commit: Option<bool>,
+    access: Option<Access>,
+    script: Option<PathBuf>,
+    without_cargo: Option<bool>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> anyhow::Result<ConfigFile> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file `{}`", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config file `{}`", path.display()))
+    }
+
+    /// Fills in the options of `opts` that were not given on the command line
+    /// with the values from this file.
+    pub fn apply(self, opts: &mut Opts, matches: &ArgMatches) -> anyhow::Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = self.$field {
+                        if !from_cli(stringify!($field)) {
+                            opts.$field = value;
+                        }
+                    }
+                )*
+            };
+        }
+        macro_rules! merge_optional {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if self.$field.is_some() && !from_cli(stringify!($field)) {
+                        opts.$field = self.$field;
+                    }
+                )*
+            };
+        }
+
+        let validators = [
+            (
+                "host",
+                &self.host,
+                validate_triple as fn(&str) -> Result<String, String>,
+            ),
+            ("target", &self.target, validate_target),
+        ];
+        for (name, triple, validate) in validators {
+            if let Some(triple) = triple {
+                validate(triple)
+                    .map_err(anyhow::Error::msg)
+                    .with_context(|| format!("invalid `{name}` in config file"))?;
+            }
+        }
+
+        merge!(
+            regress,
+            alt,
+            host,
+            preserve,
+            preserve_target,
+            with_src,
+            with_dev,
+            components,
+            prompt,
+            by_commit,
+            access,
+            without_cargo,
+        );
+        merge_optional!(target, timeout, script);
+
+        if let Some(test_dir) = self.test_dir {
+            if !from_cli("test_dir") {
+                opts.test_dir = crate::value_parsers::validate_dir(&test_dir.to_string_lossy())
+                    .context("invalid `test-dir` in config file")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn opts_with_config(cli: &[&str], config: &str) -> Opts {
+        let matches = Opts::command().get_matches_from(cli);
+        let mut opts = Opts::from_arg_matches(&matches).unwrap();
+        let file: ConfigFile = toml::from_str(config).unwrap();
+        file.apply(&mut opts, &matches).unwrap();
+        opts
+    }
+
+    #[test]
+    fn test_config_file_fills_defaults() {
+        let opts = opts_with_config(
+            &["cargo-bisect-rustc"],
+            r#"
+            host = "aarch64-unknown-linux-gnu"
+            access = "checkout"
+            preserve = true
+            regress = "non-ice"
+            components = ["rust-src", "clippy"]
+            "#,
+        );
+        assert_eq!(opts.host, "aarch64-unknown-linux-gnu");
+        assert!(matches!(opts.access, Access::Checkout));
+        assert!(opts.preserve);
+        assert_eq!(opts.regress, RegressOn::NonIce);
+        assert_eq!(opts.components, ["rust-src", "clippy"]);
+    }
+
+    #[test]
+    fn test_command_line_overrides_config_file() {
+        let opts = opts_with_config(
+            &[
+                "cargo-bisect-rustc",
+                "--host",
+                "x86_64-pc-windows-msvc",
+                "--regress",
+                "success",
+            ],
+            r#"
+            host = "aarch64-unknown-linux-gnu"
+            regress = "ice"
+            timeout = 30
+            "#,
+        );
+        assert_eq!(opts.host, "x86_64-pc-windows-msvc");
+        assert_eq!(opts.regress, RegressOn::Success);
+        assert_eq!(opts.timeout, Some(30));
+    }
+
+    #[test]
+    fn test_config_file_rejects_unknown_keys() {
+        assert!(toml::from_str::<ConfigFile>("hots = \"x\"").is_err());
+    }
+}
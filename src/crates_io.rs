@@ -0,0 +1,94 @@
+//! Fetching published crates from crates.io to use as the test project.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use flate2::read::GzDecoder;
+use reqwest::blocking::Client;
+use tar::Archive;
+use tempfile::TempDir;
+
+use crate::toolchains::download_progress;
+
+const CRATES_IO_DL: &str = "https://static.crates.io/crates";
+
+/// A crate published on crates.io, given as `NAME@VERSION`.
+#[derive(Clone, Debug)]
+pub struct CrateSpec {
+    pub name: String,
+    pub version: String,
+}
+
+impl FromStr for CrateSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.split_once('@') {
+            Some((name, version)) if !name.is_empty() && !version.is_empty() => Ok(CrateSpec {
+                name: name.to_string(),
+                version: version.to_string(),
+            }),
+            _ => bail!("expected a crate as NAME@VERSION, got `{s}`"),
+        }
+    }
+}
+
+impl fmt::Display for CrateSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+impl CrateSpec {
+    /// The name of the directory the `.crate` archive extracts to.
+    pub fn dir_name(&self) -> String {
+        format!("{}-{}", self.name, self.version)
+    }
+}
+
+/// Downloads the given crate and extracts it into a fresh temporary
+/// directory, which is left behind when dropped if `keep` is set. The crate
+/// sources end up in the `CrateSpec::dir_name` subdirectory.
+pub fn fetch_crate(client: &Client, spec: &CrateSpec, keep: bool) -> anyhow::Result<TempDir> {
+    let url = format!(
+        "{CRATES_IO_DL}/{name}/{dir_name}.crate",
+        name = spec.name,
+        dir_name = spec.dir_name()
+    );
+    network_log!("fetching {url}");
+    let dir = tempfile::Builder::new()
+        .prefix("cargo-bisect-rustc-")
+        .keep(keep)
+        .tempdir()
+        .context("failed to create a temporary directory for the test crate")?;
+    let response = download_progress(client, &spec.to_string(), &url)
+        .with_context(|| format!("failed to download {spec} from crates.io"))?;
+    Archive::new(GzDecoder::new(response))
+        .unpack(dir.path())
+        .with_context(|| format!("failed to extract {spec}"))?;
+    if !dir.path().join(spec.dir_name()).is_dir() {
+        bail!(
+            "the archive of {spec} does not contain a `{}` directory",
+            spec.dir_name()
+        );
+    }
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_crate_spec() {
+        let spec: CrateSpec = "serde@1.0.145".parse().unwrap();
+        assert_eq!(spec.name, "serde");
+        assert_eq!(spec.version, "1.0.145");
+        assert_eq!(spec.dir_name(), "serde-1.0.145");
+
+        assert!("serde".parse::<CrateSpec>().is_err());
+        assert!("serde@".parse::<CrateSpec>().is_err());
+        assert!("@1.0.0".parse::<CrateSpec>().is_err());
+    }
+}
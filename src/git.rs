@@ -5,7 +5,8 @@ const RUST_SRC_REPO: Option<&str> = option_env!("RUST_SRC_REPO");
 
 use std::env;
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use anyhow::{bail, Context};
 use chrono::{TimeZone, Utc};
@@ -13,6 +14,7 @@ use git2::build::RepoBuilder;
 use git2::{Commit as Git2Commit, Repository};
 use log::debug;
 
+use crate::toolchains::{check_network, network_disabled};
 use crate::{Author, Commit, GitDate, BORS_AUTHOR};
 
 impl Commit {
@@ -73,13 +75,20 @@ fn lookup_rev<'rev>(repo: &'rev RustcRepo, rev: &str) -> anyhow::Result<Git2Comm
 
 fn get_repo() -> anyhow::Result<RustcRepo> {
     fn open(path: &Path) -> anyhow::Result<(Repository, String)> {
-        eprintln!("opening existing repository at {:?}", path);
+        network_log!("opening existing repository at {:?}", path);
         let repo = Repository::open(path)?;
 
         let origin_remote = find_origin_remote(&repo)?;
-        eprintln!("Found origin remote under name `{origin_remote}`");
+        network_log!("Found origin remote under name `{origin_remote}`");
 
-        eprintln!("refreshing repository at {:?}", path);
+        if network_disabled() {
+            eprintln!(
+                "not refreshing the rust repository at {} because of --no-network",
+                path.display()
+            );
+            return Ok((repo, origin_remote));
+        }
+        network_log!("refreshing repository at {:?}", path);
         // This uses the CLI because libgit2 is quite slow to fetch a large repository.
         let status = std::process::Command::new("git")
             .args(&["fetch", "--tags"])
@@ -100,6 +109,7 @@ fn get_repo() -> anyhow::Result<RustcRepo> {
         (None, _) if loc.exists() => open(loc),
         (None, Some(repo)) => open(Path::new(repo)),
         _ => {
+            check_network(RUST_SRC_URL)?;
             eprintln!("cloning rust repository");
             Ok((
                 RepoBuilder::new().bare(true).clone(RUST_SRC_URL, loc)?,
@@ -133,6 +143,57 @@ Try adding a remote pointing to `{RUST_SRC_URL}` in the rust repository at `{}`.
         })
 }
 
+/// Returns the path of the local rust repository, without cloning or
+/// fetching it. Follows the same precedence as `get_repo`.
+fn local_repo_path() -> anyhow::Result<PathBuf> {
+    let loc = Path::new("rust.git");
+    match (env::var_os("RUST_SRC_REPO"), RUST_SRC_REPO) {
+        (Some(repo), _) => Ok(PathBuf::from(repo)),
+        (None, _) if loc.exists() => Ok(loc.to_path_buf()),
+        (None, Some(repo)) => Ok(PathBuf::from(repo)),
+        (None, None) => bail!("no local rust repository found; set RUST_SRC_REPO to a clone"),
+    }
+}
+
+/// Checks out `sha` in the local rust repository and initializes and updates
+/// its submodules to match, for `--checkout-submodules`.
+pub(crate) fn checkout_with_submodules(sha: &str) -> anyhow::Result<()> {
+    let path = local_repo_path()?;
+    if Repository::open(&path)?.is_bare() {
+        bail!(
+            "the rust repository at {} is bare and cannot be checked out; \
+             set RUST_SRC_REPO to a clone with a working tree",
+            path.display()
+        );
+    }
+    let git = |args: &[&str]| -> anyhow::Result<()> {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(&path)
+            .status()
+            .context("expected `git` command-line executable to be installed")?;
+        if !status.success() {
+            bail!("`git {}` failed with {status}", args.join(" "));
+        }
+        Ok(())
+    };
+    eprintln!("checking out {sha} in {}", path.display());
+    git(&["checkout", "--detach", sha])?;
+    if network_disabled() {
+        // Only the submodule commits already fetched can be checked out.
+        eprintln!("not fetching submodules because of --no-network");
+        return git(&[
+            "-c",
+            "protocol.allow=never",
+            "submodule",
+            "update",
+            "--init",
+            "--no-fetch",
+        ]);
+    }
+    git(&["submodule", "update", "--init"])
+}
+
 pub(crate) fn get_commit(sha: &str) -> anyhow::Result<Commit> {
     let repo = get_repo()?;
     let mut rev = lookup_rev(&repo, sha)?;
@@ -143,9 +204,9 @@ pub(crate) fn get_commit(sha: &str) -> anyhow::Result<Commit> {
 /// (boundaries inclusive).
 pub fn get_commits_between(first_commit: &str, last_commit: &str) -> anyhow::Result<Vec<Commit>> {
     let repo = get_repo()?;
-    eprintln!("looking up first commit");
+    network_log!("looking up first commit");
     let mut first = lookup_rev(&repo, first_commit)?;
-    eprintln!("looking up second commit");
+    network_log!("looking up second commit");
     let last = lookup_rev(&repo, last_commit)?;
 
     // Sanity check -- our algorithm below only works reliably if the
@@ -162,7 +223,7 @@ pub fn get_commits_between(first_commit: &str, last_commit: &str) -> anyhow::Res
         }
     };
 
-    eprintln!("checking that commits are by bors and thus have ci artifacts...");
+    network_log!("checking that commits are by bors and thus have ci artifacts...");
     assert_by_bors(&first)?;
     assert_by_bors(&last)?;
     // Now find the commits
@@ -170,7 +231,7 @@ pub fn get_commits_between(first_commit: &str, last_commit: &str) -> anyhow::Res
     // to only get merge commits.
     // This uses the fact that all bors merge commits have the earlier
     // merge commit as their first parent.
-    eprintln!("finding bors merge commits");
+    network_log!("finding bors merge commits");
     let mut res = Vec::new();
     let mut current = last;
     loop {
@@ -199,9 +260,38 @@ pub fn get_commits_between(first_commit: &str, last_commit: &str) -> anyhow::Res
     res.push(Commit::from_git2_commit(&mut first));
     // Reverse in order to obtain chronological order
     res.reverse();
-    eprintln!(
+    network_log!(
         "found {} bors merge commits in the specified range",
         res.len()
     );
     Ok(res)
 }
+
+/// Returns the `count` most recent bors merge commits up to and including
+/// `last_commit`, in chronological order.
+pub fn get_recent_commits(last_commit: &str, count: usize) -> anyhow::Result<Vec<Commit>> {
+    let repo = get_repo()?;
+    let mut current = lookup_rev(&repo, last_commit)?;
+    // As in `get_commits_between`, follow the first parents, which are the
+    // earlier bors merge commits.
+    network_log!("finding bors merge commits");
+    let mut res = Vec::new();
+    while res.len() < count {
+        if current.author().name() == Some(BORS_AUTHOR) {
+            res.push(Commit::from_git2_commit(&mut current));
+        } else {
+            debug!(
+                "{:?} has non-bors author: {:?}, skipping",
+                current.id(),
+                current.author().name()
+            );
+        }
+        match current.parents().next() {
+            Some(c) => current = c,
+            None => break,
+        }
+    }
+    res.reverse();
+    network_log!("found {} bors merge commits", res.len());
+    Ok(res)
+}
@@ -1,8 +1,14 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
 use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, InvalidHeaderValue, AUTHORIZATION, USER_AGENT};
-use reqwest::{blocking::Client, blocking::Response};
+use reqwest::{blocking::Client, blocking::Response, StatusCode};
 use serde::{Deserialize, Serialize};
 
+use crate::toolchains::check_network;
 use crate::{parse_to_naive_date, Author, Commit, GitDate, BORS_AUTHOR};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -75,26 +81,120 @@ fn headers() -> Result<HeaderMap, InvalidHeaderValue> {
     let user_agent = HeaderValue::from_static(user_agent);
     headers.insert(USER_AGENT, user_agent);
     if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-        eprintln!("adding local env GITHUB_TOKEN value to headers in github query");
+        network_log!("adding local env GITHUB_TOKEN value to headers in github query");
         let value = HeaderValue::from_str(&format!("token {token}"))?;
         headers.insert(AUTHORIZATION, value);
     }
     Ok(headers)
 }
 
-pub(crate) fn get_commit(sha: &str) -> anyhow::Result<Commit> {
-    let url = CommitDetailsUrl { sha }.url();
-    let client = Client::builder().default_headers(headers()?).build()?;
-    let response: Response = client.get(&url).send()?;
+/// Returns the response if it was successful, or an error describing the
+/// failure otherwise.
+fn check_response(url: &str, response: Response) -> anyhow::Result<Response> {
     let status = response.status();
-    if !status.is_success() {
-        bail!(
-            "error: url <{}> response {}: {}",
-            url,
-            status,
-            response.text().unwrap_or_else(|_| format!("<empty>"))
+    if status.is_success() {
+        return Ok(response);
+    }
+    if let Some(msg) = rate_limit_message(status, response.headers(), Utc::now()) {
+        bail!("error: url <{url}> response {status}: {msg}");
+    }
+    bail!(
+        "error: url <{}> response {}: {}",
+        url,
+        status,
+        response.text().unwrap_or_else(|_| format!("<empty>"))
+    );
+}
+
+/// How many times a request is retried after hitting a rate limit.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// The longest wait for a rate limit to reset before giving up, since the
+/// primary rate limit can take up to an hour to reset.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_mins(5);
+
+/// Sends a GET request for `url`, waiting and retrying when GitHub asks us to
+/// slow down, and returns the response if it was eventually successful.
+fn get(client: &Client, url: &str) -> anyhow::Result<Response> {
+    check_network(url)?;
+    let mut retries = 0;
+    loop {
+        let response = client.get(url).send()?;
+        let delay = retry_delay(response.status(), response.headers(), Utc::now());
+        match delay {
+            Some(delay) if retries < MAX_RATE_LIMIT_RETRIES && delay <= MAX_RATE_LIMIT_WAIT => {
+                retries += 1;
+                eprintln!(
+                    "GitHub rate limit reached, retrying in {}s \
+                     (retry {retries} of {MAX_RATE_LIMIT_RETRIES})",
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+            }
+            _ => return check_response(url, response),
+        }
+    }
+}
+
+/// If the response is a rate limit error, returns how long GitHub asks us to
+/// wait before trying again: the `Retry-After` of a secondary rate limit, or
+/// the time until `X-RateLimit-Reset` when the primary one is used up.
+fn retry_delay(status: StatusCode, headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(secs) = header("retry-after").and_then(|secs| secs.parse().ok()) {
+        return Some(Duration::from_secs(secs));
+    }
+    if header("x-ratelimit-remaining") != Some("0") {
+        return None;
+    }
+    let reset = header("x-ratelimit-reset")?.parse::<i64>().ok()?;
+    // Wait a second longer, since the reset time is rounded down.
+    let secs = (reset - now.timestamp()).max(0) + 1;
+    Some(Duration::from_secs(secs.try_into().ok()?))
+}
+
+/// If the response indicates that the GitHub API rate limit was exceeded,
+/// returns a message explaining when it resets and how to raise it.
+fn rate_limit_message(
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if header("x-ratelimit-remaining") != Some("0") {
+        return None;
+    }
+    let mut msg = String::from("GitHub API rate limit exceeded");
+    if let Some(reset) = header("x-ratelimit-reset")
+        .and_then(|reset| reset.parse().ok())
+        .and_then(|reset| DateTime::<Utc>::from_timestamp(reset, 0))
+    {
+        let minutes = (reset - now).num_minutes().max(0);
+        msg = format!(
+            "{msg}; it resets at {} (in about {minutes} minutes)",
+            reset.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+    }
+    if std::env::var_os("GITHUB_TOKEN").is_none() {
+        msg.push_str(
+            ".\nUnauthenticated requests have a low limit; \
+             set the GITHUB_TOKEN environment variable to a GitHub access token \
+             to raise it, or use --access=checkout to use a local clone instead",
         );
     }
+    Some(msg)
+}
+
+pub(crate) fn get_commit(sha: &str) -> anyhow::Result<Commit> {
+    let url = CommitDetailsUrl { sha }.url();
+    let client = Client::builder().default_headers(headers()?).build()?;
+    let response = get(&client, &url)?;
     let elem: GithubCommitComparison = response
         .json()
         .with_context(|| "failed to decode GitHub JSON response")?;
@@ -104,27 +204,27 @@ pub(crate) fn get_commit(sha: &str) -> anyhow::Result<Commit> {
 pub(crate) fn get_pr_comments(pr: &str) -> anyhow::Result<Vec<GithubComment>> {
     let url = format!("https://api.github.com/repos/rust-lang/rust/issues/{pr}/comments");
     let client = Client::builder().default_headers(headers()?).build()?;
-    let response: Response = client.get(&url).send()?;
-    let status = response.status();
-    if !status.is_success() {
-        bail!(
-            "error: url <{}> response {}: {}",
-            url,
-            status,
-            response.text().unwrap_or_else(|_| format!("<empty>"))
-        );
-    }
+    let response = get(&client, &url)?;
     let comments: Vec<GithubComment> = response
         .json()
         .with_context(|| "failed to decode GitHub JSON response")?;
     Ok(comments)
 }
 
+/// The most commits a query may fetch, set with `--commit-limit`.
+static COMMIT_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+pub(crate) fn set_commit_limit(limit: usize) {
+    COMMIT_LIMIT.store(limit, Ordering::Relaxed);
+}
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct CommitsQuery<'a> {
-    pub since_date: &'a str,
+    pub since_date: Option<&'a str>,
     pub most_recent_sha: &'a str,
-    pub earliest_sha: &'a str,
+    pub earliest_sha: Option<&'a str>,
+    /// Stop after this many commits, even if `earliest_sha` was not found.
+    pub max_commits: Option<usize>,
 }
 
 /// Returns the bors merge commits between the two specified boundaries
@@ -133,8 +233,10 @@ pub(crate) struct CommitsQuery<'a> {
 impl CommitsQuery<'_> {
     pub fn get_commits(&self) -> anyhow::Result<Vec<Commit>> {
         // build up commit sequence, by feeding in `sha` as the starting point, and
-        // working way backwards to max(`self.since_date`, `self.earliest_sha`).
+        // working way backwards to max(`self.since_date`, `self.earliest_sha`),
+        // or until `self.max_commits` have been found.
         let mut commits = Vec::new();
+        let limit = COMMIT_LIMIT.load(Ordering::Relaxed);
 
         // focus on Pull Request merges, all authored and committed by bors.
         let client = Client::builder().default_headers(headers()?).build()?;
@@ -147,27 +249,35 @@ impl CommitsQuery<'_> {
             }
             .url();
 
-            let response: Response = client.get(&url).send()?;
-            let status = response.status();
-            if !status.is_success() {
-                bail!(
-                    "error: url <{}> response {}: {}",
-                    url,
-                    status,
-                    response.text().unwrap_or_else(|_| format!("<empty>"))
-                );
-            }
+            let response = get(&client, &url)?;
 
             let action = parse_paged_elems(response, |elem: GithubCommitElem| {
-                let found_last = elem.sha == self.earliest_sha;
+                let mut found_last = self.earliest_sha == Some(elem.sha.as_str());
                 if found_last {
-                    eprintln!(
+                    network_log!(
                         "ending github query because we found starting sha: {}",
                         elem.sha
                     );
                 }
                 let commit = elem.git_commit()?;
                 commits.push(commit);
+                if self.max_commits == Some(commits.len()) {
+                    network_log!("ending github query after {} commits", commits.len());
+                    found_last = true;
+                }
+                if !found_last && commits.len() >= limit {
+                    match self.earliest_sha {
+                        Some(sha) => bail!(
+                            "fetched {limit} commits from GitHub without reaching the start {sha}; \
+                             the range is too large, or the start is not a bors merge on master. \
+                             Check --start, or raise --commit-limit"
+                        ),
+                        None => bail!(
+                            "fetching more than {limit} commits from GitHub; \
+                             raise --commit-limit to fetch more"
+                        ),
+                    }
+                }
 
                 Ok(if found_last { Loop::Break } else { Loop::Next })
             })?;
@@ -177,7 +287,7 @@ impl CommitsQuery<'_> {
             }
         }
 
-        eprintln!(
+        network_log!(
             "get_commits_between returning commits, len: {}",
             commits.len()
         );
@@ -198,7 +308,7 @@ trait ToUrl {
 struct CommitsUrl<'a> {
     page: usize,
     author: &'a str,
-    since: &'a str,
+    since: Option<&'a str>,
     sha: &'a str,
 }
 struct CommitDetailsUrl<'a> {
@@ -210,10 +320,13 @@ impl ToUrl for CommitsUrl<'_> {
         format!(
             "https://api.github.com/repos/{OWNER}/{REPO}/commits\
                  ?page={page}&per_page={PER_PAGE}\
-                 &author={author}&since={since}&sha={sha}",
+                 &author={author}{since}&sha={sha}",
             page = self.page,
             author = self.author,
-            since = self.since,
+            since = self
+                .since
+                .map(|s| format!("&since={s}"))
+                .unwrap_or_default(),
             sha = self.sha
         )
     }
@@ -268,6 +381,63 @@ fn parse_paged_elems(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rate_limit_message() {
+        let now = DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("1700000600"));
+
+        let msg = rate_limit_message(StatusCode::FORBIDDEN, &headers, now).unwrap();
+        assert!(msg.starts_with("GitHub API rate limit exceeded"));
+        assert!(msg.contains("2023-11-14 22:23:20 UTC (in about 10 minutes)"));
+
+        assert!(rate_limit_message(StatusCode::NOT_FOUND, &headers, now).is_none());
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("12"));
+        assert!(rate_limit_message(StatusCode::FORBIDDEN, &headers, now).is_none());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let now = DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("30"));
+        assert_eq!(
+            retry_delay(StatusCode::FORBIDDEN, &headers, now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, &headers, now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(retry_delay(StatusCode::NOT_FOUND, &headers, now), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("1700000600"));
+        assert_eq!(
+            retry_delay(StatusCode::FORBIDDEN, &headers, now),
+            Some(Duration::from_secs(601))
+        );
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("12"));
+        assert_eq!(retry_delay(StatusCode::FORBIDDEN, &headers, now), None);
+    }
+
+    #[test]
+    fn test_commits_url() {
+        let url = |since| {
+            CommitsUrl {
+                page: 2,
+                author: BORS_AUTHOR,
+                since,
+                sha: "master",
+            }
+            .url()
+        };
+        assert!(url(Some("2023-01-01")).ends_with("&author=bors&since=2023-01-01&sha=master"));
+        assert!(url(None).ends_with("&author=bors&sha=master"));
+    }
+
     #[test]
     fn test_github() {
         let c = get_commit("25674202bb7415e0c0ecd07856749cfb7f591be6").unwrap();
@@ -1,20 +1,44 @@
+use std::cell::Cell;
 use std::collections::BTreeMap;
 use std::fmt;
 
-pub fn least_satisfying<T, P>(slice: &[T], mut predicate: P) -> usize
+/// Returns the index of the first element of `slice` that satisfies
+/// `predicate`, presuming the first element does not and the last one does.
+///
+/// `on_narrow` is called with the rightmost known "no", the leftmost known
+/// "yes", and the number of elements left between them every time one of
+/// them moves.
+///
+/// `bias` only selects the first element to test; the search continues with
+/// plain bisection from there.
+///
+/// If `max_steps` is given, at most that many elements are tested; when the
+/// budget runs out before the search converges, the range narrowed down so
+/// far is returned instead.
+pub fn least_satisfying<T, P, N>(
+    slice: &[T],
+    bias: Bias,
+    max_steps: Option<usize>,
+    mut predicate: P,
+    mut on_narrow: N,
+) -> SearchResult
 where
     T: fmt::Display + fmt::Debug,
     P: FnMut(&T, usize, usize) -> Satisfies,
+    N: FnMut(&T, &T, usize),
 {
     let mut cache = BTreeMap::new();
+    let steps = Cell::new(0);
+    let has_budget = || max_steps.is_none_or(|max| steps.get() < max);
     let mut predicate = |idx: usize, rm_no, lm_yes| {
         let range: usize = lm_yes - rm_no + 1;
         // FIXME: This does not consider unknown_ranges.
         let remaining = range / 2;
         let estimate = if range < 3 { 0 } else { range.ilog2() as usize };
-        *cache
-            .entry(idx)
-            .or_insert_with(|| predicate(&slice[idx], remaining, estimate))
+        *cache.entry(idx).or_insert_with(|| {
+            steps.set(steps.get() + 1);
+            predicate(&slice[idx], remaining, estimate)
+        })
     };
     let mut unknown_ranges: Vec<(usize, usize)> = Vec::new();
     // presume that the slice starts with a no
@@ -25,17 +49,17 @@ where
     // this should be tested before the call
     let mut lm_yes = slice.len() - 1;
 
-    let mut next = (rm_no + lm_yes) / 2;
+    let mut next = bias.first_probe(slice.len());
 
     loop {
         // simple case with no unknown ranges
         if rm_no + 1 == lm_yes {
-            return lm_yes;
+            return SearchResult::Found(lm_yes);
         }
         for (left, right) in unknown_ranges.iter().copied() {
             // if we're straddling an unknown range, then pretend it doesn't exist
             if rm_no + 1 == left && right + 1 == lm_yes {
-                return lm_yes;
+                return SearchResult::Found(lm_yes);
             }
             // check if we're checking inside an unknown range and set the next check outside of it
             if left <= next && next <= right {
@@ -48,41 +72,160 @@ where
             }
         }
 
+        if !has_budget() {
+            return SearchResult::Interrupted {
+                last_no: rm_no,
+                first_yes: lm_yes,
+            };
+        }
+
         let r = predicate(next, rm_no, lm_yes);
         match r {
             Satisfies::Yes => {
                 lm_yes = next;
                 next = (rm_no + lm_yes) / 2;
+                on_narrow(&slice[rm_no], &slice[lm_yes], lm_yes - rm_no - 1);
             }
             Satisfies::No => {
                 rm_no = next;
                 next = (rm_no + lm_yes) / 2;
+                on_narrow(&slice[rm_no], &slice[lm_yes], lm_yes - rm_no - 1);
             }
             Satisfies::Unknown => {
-                let mut left = next;
-                while left > 0 && predicate(left, rm_no, lm_yes) == Satisfies::Unknown {
-                    left -= 1;
+                // Find the run of unknown elements around `next`. The walks
+                // stay strictly between `rm_no` and `lm_yes`, whose results
+                // are already known, so `lo <= next <= hi` always holds and
+                // the ends of the slice are never tested.
+                let mut lo = next;
+                while lo - 1 > rm_no
+                    && has_budget()
+                    && predicate(lo - 1, rm_no, lm_yes) == Satisfies::Unknown
+                {
+                    lo -= 1;
                 }
-                let mut right = next;
-                while right + 1 < slice.len()
-                    && predicate(right, rm_no, lm_yes) == Satisfies::Unknown
+                let mut hi = next;
+                while hi + 1 < lm_yes
+                    && has_budget()
+                    && predicate(hi + 1, rm_no, lm_yes) == Satisfies::Unknown
                 {
-                    right += 1;
+                    hi += 1;
                 }
-                unknown_ranges.push((left + 1, right - 1));
-                next = left;
+                debug_assert!(rm_no < lo && lo <= hi && hi < lm_yes);
+                unknown_ranges.push((lo, hi));
+                // Continue next to the run, with an element whose result is
+                // (or, if the budget ran out, will be) known.
+                next = if lo - 1 > rm_no { lo - 1 } else { hi + 1 };
             }
         }
     }
 }
 
+/// The result of `least_satisfying`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SearchResult {
+    /// The index of the first element satisfying the predicate.
+    Found(usize),
+    /// The step budget ran out. The first element satisfying the predicate is
+    /// after `last_no`, and no later than `first_yes`.
+    Interrupted { last_no: usize, first_yes: usize },
+}
+
+/// Which part of the range to test first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Bias {
+    /// A quarter of the way into the range.
+    Start,
+    /// The middle of the range.
+    Middle,
+    /// Three quarters of the way into the range.
+    End,
+}
+
+impl Bias {
+    /// The index of the first element tested in a slice of length `len`,
+    /// whose first and last elements are presumed to be known.
+    pub fn first_probe(self, len: usize) -> usize {
+        let last = len - 1;
+        if last < 2 {
+            // There is nothing between the known ends to test.
+            return last / 2;
+        }
+        let probe = match self {
+            Bias::Start => last / 4,
+            Bias::Middle => last / 2,
+            Bias::End => last - last / 4,
+        };
+        // Never test the ends, they are already known.
+        probe.clamp(1, last - 1)
+    }
+}
+
+/// Returns the indices that `least_satisfying` tests after `next`, if it
+/// satisfies the predicate and if it does not, given the rightmost known "no"
+/// and the leftmost known "yes". Unknown results are not predicted.
+pub fn next_probes(rm_no: usize, next: usize, lm_yes: usize) -> Vec<usize> {
+    [(rm_no, next), (next, lm_yes)]
+        .into_iter()
+        .filter(|&(no, yes)| no + 1 < yes)
+        .map(|(no, yes)| usize::midpoint(no, yes))
+        .collect()
+}
+
+/// Returns the indices sampled to check that a range of length `len` is
+/// monotonic: the first probes of every `Bias`, which leave out the ends.
+pub fn monotonicity_samples(len: usize) -> Vec<usize> {
+    let mut samples: Vec<usize> = [Bias::Start, Bias::Middle, Bias::End]
+        .into_iter()
+        .map(|bias| bias.first_probe(len))
+        .filter(|&idx| idx > 0 && idx + 1 < len)
+        .collect();
+    samples.dedup();
+    samples
+}
+
+/// Given results sorted by index, returns the first element that satisfies
+/// the predicate followed by a later one that does not, which means the
+/// range is not monotonic. Unknown results are ignored.
+pub fn find_nonmonotonic(results: &[(usize, Satisfies)]) -> Option<(usize, usize)> {
+    let first_yes = results
+        .iter()
+        .find(|(_, r)| *r == Satisfies::Yes)
+        .map(|&(idx, _)| idx)?;
+    results
+        .iter()
+        .find(|&&(idx, r)| idx > first_yes && r == Satisfies::No)
+        .map(|&(idx, _)| (first_yes, idx))
+}
+
 #[cfg(test)]
 mod tests {
     use super::Satisfies::{No, Unknown, Yes};
-    use super::{least_satisfying, Satisfies};
+    use super::{
+        find_nonmonotonic, monotonicity_samples, next_probes, Bias, Satisfies, SearchResult,
+    };
     use quickcheck::{QuickCheck, TestResult};
 
+    fn least_satisfying<T, P>(slice: &[T], predicate: P) -> usize
+    where
+        T: std::fmt::Display + std::fmt::Debug,
+        P: FnMut(&T, usize, usize) -> Satisfies,
+    {
+        match super::least_satisfying(slice, Bias::Middle, None, predicate, |_, _, _| {}) {
+            SearchResult::Found(idx) => idx,
+            result @ SearchResult::Interrupted { .. } => panic!("unexpected {result:?}"),
+        }
+    }
+
     fn prop(xs: Vec<Option<bool>>) -> TestResult {
+        prop_with_bias(xs, Bias::Middle)
+    }
+
+    fn prop_biased(xs: Vec<Option<bool>>, towards_end: bool) -> TestResult {
+        let bias = if towards_end { Bias::End } else { Bias::Start };
+        prop_with_bias(xs, bias)
+    }
+
+    fn prop_with_bias(xs: Vec<Option<bool>>, bias: Bias) -> TestResult {
         let mut satisfies_v = xs
             .into_iter()
             .map(std::convert::Into::into)
@@ -99,9 +242,9 @@ mod tests {
             }
         }
 
-        let res = least_satisfying(&satisfies_v, |i, _, _| *i);
+        let res = super::least_satisfying(&satisfies_v, bias, None, |i, _, _| *i, |_, _, _| {});
         let exp = first_yes.unwrap();
-        TestResult::from_bool(res == exp)
+        TestResult::from_bool(res == SearchResult::Found(exp))
     }
 
     #[test]
@@ -159,10 +302,190 @@ mod tests {
         );
     }
 
+    #[test]
+    fn least_satisfying_reports_narrowing() {
+        let slice = [No, No, No, Unknown, Yes, Yes, Yes, Yes];
+        let indices: Vec<usize> = (0..slice.len()).collect();
+        let mut ranges = Vec::new();
+        let found = super::least_satisfying(
+            &indices,
+            Bias::Middle,
+            None,
+            |&i, _, _| slice[i],
+            |&no, &yes, between| {
+                assert_eq!(between, yes - no - 1);
+                ranges.push((no, yes));
+            },
+        );
+        assert_eq!(found, SearchResult::Found(4));
+        assert_eq!(ranges.last(), Some(&(2, 4)));
+        for pair in ranges.windows(2) {
+            let ((no_a, yes_a), (no_b, yes_b)) = (pair[0], pair[1]);
+            assert!(no_a <= no_b && yes_b <= yes_a);
+        }
+    }
+
+    #[test]
+    fn next_probes_predicts_the_next_probe() {
+        assert_eq!(next_probes(0, 5, 10), [2, 7]);
+        assert_eq!(next_probes(0, 1, 2), Vec::<usize>::new());
+        assert_eq!(next_probes(3, 4, 6), [5]);
+
+        for len in 3..40 {
+            for first_yes in 1..len {
+                let indices: Vec<usize> = (0..len).collect();
+                let bounds = std::cell::Cell::new((0, len - 1));
+                let mut predicted: Option<Vec<usize>> = None;
+                super::least_satisfying(
+                    &indices,
+                    Bias::Middle,
+                    None,
+                    |&i, _, _| {
+                        if let Some(predicted) = predicted.take() {
+                            assert!(predicted.contains(&i), "{i} not in {predicted:?}");
+                        }
+                        let (rm_no, lm_yes) = bounds.get();
+                        predicted = Some(next_probes(rm_no, i, lm_yes));
+                        Satisfies::from(Some(i >= first_yes))
+                    },
+                    |&no, &yes, _| bounds.set((no, yes)),
+                );
+            }
+        }
+    }
+
+    /// Runs `least_satisfying` over `slice`, checking that every element is
+    /// tested at most once and that the presumed ends are never tested.
+    fn least_satisfying_probes(slice: &[Satisfies]) -> usize {
+        let indices: Vec<usize> = (0..slice.len()).collect();
+        let mut probed = Vec::new();
+        let result = super::least_satisfying(
+            &indices,
+            Bias::Middle,
+            None,
+            |&i, _, _| {
+                probed.push(i);
+                slice[i]
+            },
+            |_, _, _| {},
+        );
+        let mut unique = probed.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(
+            unique.len(),
+            probed.len(),
+            "retested an element: {probed:?}"
+        );
+        assert!(!probed.contains(&0), "tested the start: {probed:?}");
+        assert!(
+            !probed.contains(&(slice.len() - 1)),
+            "tested the end: {probed:?}"
+        );
+        match result {
+            SearchResult::Found(idx) => idx,
+            result @ SearchResult::Interrupted { .. } => panic!("unexpected {result:?}"),
+        }
+    }
+
+    #[test]
+    fn least_satisfying_unknown_at_start() {
+        assert_eq!(
+            least_satisfying_probes(&[No, Unknown, Unknown, Unknown, Yes, Yes, Yes]),
+            4
+        );
+        assert_eq!(least_satisfying_probes(&[No, Unknown, Yes, Yes]), 2);
+    }
+
+    #[test]
+    fn least_satisfying_unknown_at_end() {
+        assert_eq!(
+            least_satisfying_probes(&[No, No, No, Unknown, Unknown, Unknown, Yes]),
+            6
+        );
+        assert_eq!(least_satisfying_probes(&[No, No, Unknown, Yes]), 3);
+    }
+
+    #[test]
+    fn least_satisfying_unknown_island() {
+        assert_eq!(least_satisfying_probes(&[No, No, Unknown, No, Yes, Yes]), 4);
+        assert_eq!(least_satisfying_probes(&[No, Unknown, Yes]), 2);
+    }
+
+    #[test]
+    fn least_satisfying_all_unknown() {
+        assert_eq!(
+            least_satisfying_probes(&[No, Unknown, Unknown, Unknown, Unknown, Yes]),
+            5
+        );
+    }
+
+    #[test]
+    fn least_satisfying_max_steps() {
+        let slice = [No, No, No, No, No, No, Yes, Yes, Yes];
+        let mut tested = 0;
+        let result = super::least_satisfying(
+            &slice,
+            Bias::Middle,
+            Some(2),
+            |i, _, _| {
+                tested += 1;
+                *i
+            },
+            |_, _, _| {},
+        );
+        assert_eq!(tested, 2);
+        assert_eq!(
+            result,
+            SearchResult::Interrupted {
+                last_no: 4,
+                first_yes: 6
+            }
+        );
+
+        let result =
+            super::least_satisfying(&slice, Bias::Middle, Some(10), |i, _, _| *i, |_, _, _| {});
+        assert_eq!(result, SearchResult::Found(6));
+    }
+
     #[test]
     fn qc_prop() {
         QuickCheck::new().quickcheck(prop as fn(_) -> _);
     }
+
+    #[test]
+    fn qc_prop_biased() {
+        QuickCheck::new().quickcheck(prop_biased as fn(_, _) -> _);
+    }
+
+    #[test]
+    fn bias_first_probe() {
+        assert_eq!(Bias::Start.first_probe(9), 2);
+        assert_eq!(Bias::Middle.first_probe(9), 4);
+        assert_eq!(Bias::End.first_probe(9), 6);
+        for bias in [Bias::Start, Bias::Middle, Bias::End] {
+            assert_eq!(bias.first_probe(2), 0);
+            assert_eq!(bias.first_probe(3), 1);
+        }
+    }
+
+    #[test]
+    fn nonmonotonic_detection() {
+        assert_eq!(monotonicity_samples(9), [2, 4, 6]);
+        assert_eq!(monotonicity_samples(4), [1, 2]);
+        assert_eq!(monotonicity_samples(3), [1]);
+        assert!(monotonicity_samples(2).is_empty());
+
+        assert_eq!(
+            find_nonmonotonic(&[(0, No), (2, No), (4, Yes), (8, Yes)]),
+            None
+        );
+        assert_eq!(find_nonmonotonic(&[(0, No), (2, Unknown), (8, Yes)]), None);
+        assert_eq!(
+            find_nonmonotonic(&[(0, No), (2, Yes), (4, Unknown), (6, No), (8, Yes)]),
+            Some((2, 6))
+        );
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
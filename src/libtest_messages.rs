@@ -0,0 +1,92 @@
+//! Reading libtest's `--format=json` output, to judge the outcome of a test
+//! by a single test with `--test-name`.
+
+use std::ffi::OsString;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum LibtestMessage {
+    Test {
+        name: String,
+        event: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// The arguments that run the tests matching `name` with `cargo test`, with
+/// libtest's JSON output. The output format is unstable, but the nightly and
+/// CI toolchains accept it.
+pub(crate) fn cargo_test_args(name: &str) -> Vec<OsString> {
+    ["test", name, "--", "-Zunstable-options", "--format=json"]
+        .into_iter()
+        .map(OsString::from)
+        .collect()
+}
+
+/// Whether the test `name` passed, by its full path or by its last
+/// component. `None` if it did not run, for example because the tests failed
+/// to build. A test that ran in several test binaries passes only if it
+/// passed in all of them.
+pub(crate) fn test_passed(stdout: &str, name: &str) -> Option<bool> {
+    let mut passed = None;
+    for line in stdout.lines().filter(|line| line.starts_with('{')) {
+        let Ok(LibtestMessage::Test { name: test, event }) = serde_json::from_str(line) else {
+            continue;
+        };
+        let matches = test == name
+            || test
+                .strip_suffix(name)
+                .is_some_and(|path| path.ends_with("::"));
+        if !matches {
+            continue;
+        }
+        match event.as_str() {
+            "ok" => passed = Some(passed.unwrap_or(true)),
+            "failed" | "timeout" => passed = Some(false),
+            _ => {}
+        }
+    }
+    passed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_passed() {
+        let stdout = r#"{"reason":"compiler-artifact","target":{"name":"my_crate"}}
+{ "type": "suite", "event": "started", "test_count": 3 }
+{ "type": "test", "event": "started", "name": "tests::parses" }
+{ "type": "test", "event": "started", "name": "tests::parses_empty" }
+{ "type": "test", "event": "started", "name": "tests::formats" }
+{ "type": "test", "name": "tests::parses", "event": "ok" }
+{ "type": "test", "name": "tests::parses_empty", "event": "ignored" }
+{ "type": "test", "name": "tests::formats", "event": "failed", "stdout": "panicked" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "ignored": 1 }
+"#;
+        assert_eq!(test_passed(stdout, "tests::parses"), Some(true));
+        assert_eq!(test_passed(stdout, "parses"), Some(true));
+        assert_eq!(test_passed(stdout, "formats"), Some(false));
+        assert_eq!(test_passed(stdout, "parses_empty"), None);
+        assert_eq!(test_passed(stdout, "ses"), None);
+        assert_eq!(test_passed(stdout, "missing"), None);
+    }
+
+    #[test]
+    fn test_cargo_test_args() {
+        assert_eq!(
+            cargo_test_args("tests::parses"),
+            [
+                "test",
+                "tests::parses",
+                "--",
+                "-Zunstable-options",
+                "--format=json"
+            ]
+        );
+    }
+}
@@ -4,40 +4,81 @@
 #![allow(clippy::let_underscore_drop)]
 #![allow(clippy::single_match_else)]
 
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::env;
 use std::ffi::OsString;
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
-use std::process;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 use anyhow::{bail, Context};
 use chrono::{Duration, NaiveDate, Utc};
-use clap::{ArgAction, Parser, ValueEnum};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ColorChoice, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use colored::Colorize;
 use github::get_pr_comments;
 use log::debug;
 use regex::RegexBuilder;
 use reqwest::blocking::Client;
+use reqwest::header::{HeaderName, HeaderValue};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+/// Prints a message about network activity, such as a URL being fetched.
+/// These are only shown with `-v`, to keep the default output focused on the
+/// tests and their results.
+macro_rules! network_log {
+    ($($arg:tt)*) => {
+        if $crate::verbose_network() {
+            eprintln!($($arg)*);
+        }
+    };
+}
 
 mod bounds;
+mod cargo_messages;
+mod config_file;
+mod crates_io;
 mod git;
 mod github;
 mod least_satisfying;
+mod libtest_messages;
 mod repo_access;
+mod report;
+mod review;
 mod toolchains;
+mod value_parsers;
 
-use crate::bounds::{Bound, Bounds};
+use crate::bounds::{warn_if_ci_expired, Bound, Bounds};
+use crate::config_file::ConfigFile;
+use crate::crates_io::CrateSpec;
 use crate::github::get_commit;
-use crate::least_satisfying::{least_satisfying, Satisfies};
+use crate::least_satisfying::{
+    find_nonmonotonic, least_satisfying, monotonicity_samples, next_probes, Bias, Satisfies,
+    SearchResult,
+};
 use crate::repo_access::{AccessViaGithub, AccessViaLocalGit, RustRepositoryAccessor};
 use crate::toolchains::{
-    parse_to_naive_date, DownloadError, DownloadParams, InstallError, TestOutcome, Toolchain,
-    ToolchainSpec, YYYY_MM_DD,
+    ci_artifacts_exist, ci_components, display_sha, nightly_artifacts_exist, nightly_components,
+    parse_to_naive_date, DownloadError, DownloadParams, FirstRuns, InstallError, TestOutcome,
+    TestRun, Toolchain, ToolchainSpec, YYYY_MM_DD,
+};
+use crate::value_parsers::{
+    check_known_triple, is_target_spec, validate_ci_url_template, validate_dir, validate_seconds,
+    validate_target, validate_triple, TripleCheck,
 };
 
 const BORS_AUTHOR: &str = "bors";
 
+/// How long CI artifacts are kept on the server.
+const CI_RETENTION_DAYS: i64 = 167;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Commit {
     pub sha: String,
@@ -72,7 +113,7 @@ const REPORT_HEADER: &str = "\
 #[derive(Debug, Parser)]
 #[command(
     bin_name = "cargo bisect-rustc",
-    version,
+    version = env!("CARGO_BISECT_VERSION"),
     about,
     next_display_order = None,
     after_help = "Examples:
@@ -89,6 +130,12 @@ const REPORT_HEADER: &str = "\
 )]
 #[allow(clippy::struct_excessive_bools)]
 struct Opts {
+    #[arg(
+        long,
+        help = "TOML file with default values for options not given on the command line"
+    )]
+    config: Option<PathBuf>,
+
     #[arg(
         long,
         help = "Custom regression definition",
@@ -97,25 +144,202 @@ struct Opts {
     )]
     regress: RegressOn,
 
+    #[arg(
+        long,
+        help = "Search for the first toolchain where the --regress condition stops holding, \
+                instead of where it starts",
+        conflicts_with = "prompt"
+    )]
+    reverse: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Decide the outcome by whether this file exists after the test, relative to \
+                --test-dir; `{toolchain}` is replaced by the toolchain name. The file is \
+                removed before each test",
+        conflicts_with = "regress"
+    )]
+    check_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "STR",
+        help = "Also require the --check-file to contain this string",
+        requires = "check_file"
+    )]
+    check_file_contains: Option<String>,
+
+    #[arg(
+        long,
+        help = "Treat a matching --check-file as a regression instead of as the baseline",
+        requires = "check_file"
+    )]
+    check_file_invert: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Only count the errors and ICEs of this crate, read from cargo's JSON output",
+        conflicts_with_all = ["script", "minimal"]
+    )]
+    regress_crate: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LINT[:LEVEL]",
+        value_parser = cargo_messages::LintMatch::parse,
+        help = "Regress when this lint fires, at the given level (warn or deny) if any, read \
+                from cargo's JSON output",
+        conflicts_with_all = ["script", "minimal", "regress", "check_file"]
+    )]
+    regress_lint: Option<cargo_messages::LintMatch>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Run `cargo test NAME` and judge the outcome by whether that test passed, read \
+                from libtest's JSON output",
+        conflicts_with_all = [
+            "script",
+            "minimal",
+            "tool",
+            "command_args",
+            "regress_lint",
+            "check_file",
+            "compare_tool",
+        ]
+    )]
+    test_name: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Decide the outcome by running this program after each test, with the test's \
+                output in files named by environment variables: exit code 0 is the baseline, \
+                1 a regression, and anything else unknown",
+        conflicts_with_all = ["regress", "check_file", "regress_lint"]
+    )]
+    compare_tool: Option<PathBuf>,
+
     #[arg(short, long, help = "Download the alt build instead of normal build")]
     alt: bool,
 
     #[arg(
         long,
-        help = "Host triple for the compiler",
+        help = "Host triple for the compiler [default: the host of the installed rustc]",
         default_value = env!("HOST"),
+        hide_default_value = true,
+        value_parser = validate_triple,
     )]
     host: String,
 
-    #[arg(long, help = "Cross-compilation target platform")]
+    #[arg(
+        long,
+        help = "Cross-compilation target platform, or the path of a custom target \
+                specification ending in `.json`",
+        value_parser = validate_target
+    )]
     target: Option<String>,
 
+    #[arg(
+        long,
+        value_name = "TARGET",
+        help = "Also test this target, counting a regression only if it passes there",
+        value_parser = validate_target,
+        requires = "target",
+        conflicts_with = "prompt"
+    )]
+    baseline_target: Option<String>,
+
+    #[arg(
+        long,
+        help = "Do not install the standard library for --target, only for the host",
+        requires = "target"
+    )]
+    no_target_std: bool,
+
+    #[arg(
+        long,
+        help = "Do not install the prebuilt standard library for --target, and install \
+                rust-src instead, for tests that build it with -Zbuild-std",
+        requires = "target",
+        conflicts_with = "no_target_std"
+    )]
+    no_prebuilt_std: bool,
+
+    #[arg(
+        long,
+        value_name = "FLAGS",
+        help = "Flags to pass to rustc, such as `-C target-cpu=native`, added to any \
+                RUSTFLAGS already set"
+    )]
+    rustflags: Vec<String>,
+
     #[arg(long, help = "Preserve the downloaded artifacts")]
     preserve: bool,
 
+    #[arg(
+        long,
+        help = "File with the format of the final report, using placeholders such as \
+                {regressed_commit} and {compare_url}"
+    )]
+    report_template: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ReportFormat::Summary,
+        conflicts_with = "report_template",
+        help = "Format of the final report"
+    )]
+    report: ReportFormat,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Keep at most this many bytes of the stdout and of the stderr of each test \
+                when they are captured",
+        default_value_t = 256 * 1024 * 1024
+    )]
+    max_output_bytes: u64,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Write the output of the tests of each toolchain to a file named after it in \
+                this directory"
+    )]
+    log_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = KeepLogs::All,
+        requires = "log_dir",
+        help = "Which logs to keep in --log-dir once the bisection is done"
+    )]
+    keep_logs: KeepLogs,
+
     #[arg(long, help = "Preserve the target directory used for builds")]
     preserve_target: bool,
 
+    #[arg(
+        long,
+        value_name = "N",
+        help = "With --preserve-target, keep only the N most recently used target directories",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "preserve_target"
+    )]
+    max_target_dirs: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Test each toolchain in a fresh copy of --test-dir, so that changes made by one \
+                test are not seen by the next one"
+    )]
+    isolate: bool,
+
     #[arg(long, help = "Download rust-src [default: no download]")]
     with_src: bool,
 
@@ -133,9 +357,39 @@ struct Opts {
     )]
     test_dir: PathBuf,
 
+    #[arg(
+        long,
+        help = "Directory to extract downloaded toolchains into before installing them \
+                [default: RUSTUP_HOME/tmp]",
+        value_parser = validate_dir
+    )]
+    tmp_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "rustup `toolchains` directory to install toolchains into, whose parent is \
+                used as RUSTUP_HOME when testing [default: RUSTUP_HOME/toolchains]",
+        value_parser = validate_dir
+    )]
+    install_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Test the build of a crate published on crates.io, given as NAME@VERSION",
+        conflicts_with = "test_dir"
+    )]
+    test_crate: Option<CrateSpec>,
+
     #[arg(long, help = "Manually evaluate for regression with prompts")]
     prompt: bool,
 
+    #[arg(
+        long,
+        visible_alias = "non-interactive",
+        help = "Answer every prompt with its default choice instead of waiting for input"
+    )]
+    assume_yes: bool,
+
     #[arg(
         long,
         short,
@@ -143,11 +397,45 @@ struct Opts {
     )]
     timeout: Option<usize>,
 
-    #[arg(short, long = "verbose", action = ArgAction::Count)]
+    #[arg(
+        long,
+        help = "Build duration threshold in seconds used by `--regress=slower`",
+        value_parser = validate_seconds
+    )]
+    max_build_seconds: Option<f64>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "How to interpret an ICE, overriding the behavior of the --regress mode"
+    )]
+    ice_is: Option<IceIs>,
+
+    #[arg(
+        long,
+        help = "Print why each tested toolchain was judged baseline or regressed"
+    )]
+    explain: bool,
+
+    #[arg(
+        short,
+        long = "verbose",
+        action = ArgAction::Count,
+        help = "Print more: -v shows the commands run and network activity, -vv also the test output"
+    )]
     verbosity: u8,
 
     #[arg(
-        help = "Arguments to pass to cargo or the file specified by --script during tests",
+        long,
+        value_enum,
+        help = "Coloring of the output",
+        default_value_t = ColorChoice::Auto
+    )]
+    color: ColorChoice,
+
+    #[arg(
+        help = "Arguments to pass to cargo or the file specified by --script during tests; \
+                relative paths in them are relative to --test-dir",
         num_args = 1..,
         last = true
     )]
@@ -167,119 +455,1103 @@ a date (YYYY-MM-DD), git tag name (e.g. 1.58.0) or git commit SHA."
     )]
     end: Option<Bound>,
 
+    #[arg(
+        long,
+        help = "Treat --end as the first toolchain known to regress and search the range \
+                just before it, ending at the previous nightly or commit",
+        requires = "end",
+        overrides_with = "end_inclusive"
+    )]
+    end_exclusive: bool,
+
+    #[arg(
+        long,
+        help = "Include --end in the searched range, so that it must reproduce the regression \
+                [default]",
+        overrides_with = "end_exclusive"
+    )]
+    end_inclusive: bool,
+
+    #[arg(
+        long,
+        alias = "since-commit-count",
+        value_name = "N",
+        help = "Bisect the last N bors merges up to --end (default: the tip of master)",
+        conflicts_with = "start"
+    )]
+    last: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "YYYY-MM-DD",
+        help = "A nightly already known not to have the regression, used as the start of the \
+                range without testing it again, instead of searching back for one"
+    )]
+    known_good_date: Option<GitDate>,
+
+    #[arg(
+        long,
+        value_name = "DAYS",
+        help = "When searching back for a nightly without the regression, first step back by \
+                this many days at a time, for a week",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    finder_initial_step: u32,
+
+    #[arg(
+        long,
+        value_name = "DAYS",
+        help = "When searching back for a nightly without the regression, step back by two \
+                weeks at a time once this many days back, and by one week before that",
+        default_value_t = 49,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    finder_coarse_after: u32,
+
+    #[arg(
+        long,
+        help = "Build twice with incremental compilation and judge the second build",
+        conflicts_with = "minimal"
+    )]
+    incremental: bool,
+
     #[arg(long, help = "Bisect via commit artifacts")]
     by_commit: bool,
 
-    #[arg(long, value_enum, help = "How to access Rust git repository", default_value_t = Access::Github)]
-    access: Access,
+    #[arg(
+        long,
+        help = "Number of times to test each toolchain",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    repeat: u32,
 
-    #[arg(long, help = "Install the given artifact")]
-    install: Option<Bound>,
+    #[arg(
+        long,
+        value_enum,
+        help = "How to combine the outcomes of repeated tests",
+        default_value_t = RepeatPolicy::All
+    )]
+    repeat_policy: RepeatPolicy,
 
-    #[arg(long, help = "Force installation over existing artifacts")]
-    force_install: bool,
+    #[arg(
+        long,
+        help = "Stop after testing this many toolchains while bisecting, and report the \
+                remaining range"
+    )]
+    max_steps: Option<usize>,
 
-    #[arg(long, help = "Script replacement for `cargo build` command")]
-    script: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        alias = "bisect-direction",
+        help = "Which part of the range to test first",
+        default_value_t = Bias::Middle
+    )]
+    bias: Bias,
 
-    #[arg(long, help = "Do not install cargo [default: install cargo]")]
-    without_cargo: bool,
+    #[arg(
+        long,
+        help = "Command to run with the baseline and regressed toolchains each time the \
+                search range narrows"
+    )]
+    on_narrow: Option<PathBuf>,
 
     #[arg(
         long,
-        help = "Text shown when a test does match the condition requested"
+        help = "Command to run with the toolchain and its result after each step of the \
+                bisection, for example to reset a device under test"
     )]
-    term_new: Option<String>,
+    step_command: Option<PathBuf>,
 
     #[arg(
         long,
-        help = "Text shown when a test fails to match the condition requested"
+        help = "Stop the bisection if --step-command fails, instead of only warning",
+        requires = "step_command"
     )]
-    term_old: Option<String>,
-}
+    step_command_fatal: bool,
 
-pub type GitDate = NaiveDate;
+    #[arg(
+        long,
+        help = "Number of characters of commit SHAs to display [default: full SHA]",
+        value_parser = clap::value_parser!(u8).range(7..=40)
+    )]
+    sha_length: Option<u8>,
 
-pub fn today() -> NaiveDate {
-    Utc::now().date_naive()
-}
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 5000,
+        help = "Most commits to fetch from GitHub for a range before giving up"
+    )]
+    commit_limit: usize,
 
-fn validate_dir(s: &str) -> anyhow::Result<PathBuf> {
-    let path: PathBuf = s.parse()?;
-    if path.is_dir() {
-        Ok(path)
-    } else {
-        bail!(
-            "{} is not an existing directory",
-            path.canonicalize()?.display()
-        )
-    }
-}
+    #[arg(
+        long,
+        value_enum,
+        help = "Whether to bisect nightlies by date or CI builds by commit",
+        default_value_t = BisectBy::Auto
+    )]
+    bisect_by: BisectBy,
 
-impl Opts {
-    fn emit_cargo_output(&self) -> bool {
-        self.verbosity >= 2
-    }
+    #[arg(long, value_enum, help = "How to access Rust git repository", default_value_t = Access::Github)]
+    access: Access,
 
-    fn emit_cmd(&self) -> bool {
-        self.verbosity >= 1
-    }
-}
+    #[arg(
+        long,
+        help = "Check out each tested commit in the local rust repository and update its \
+                submodules (requires --access=checkout)"
+    )]
+    checkout_submodules: bool,
 
-#[derive(Debug, thiserror::Error)]
-struct ExitError(i32);
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Bisect the CI builds of the commits listed in this file, one per line, \
+                oldest first",
+        conflicts_with_all = ["start", "end", "install", "check_bounds", "list_components"]
+    )]
+    commits_file: Option<PathBuf>,
 
-impl fmt::Display for ExitError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "exiting with {}", self.0)
-    }
-}
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Replay the bisection recorded in this file, without installing or testing anything",
+        conflicts_with_all = ["install", "check_bounds", "list_components", "commits_file"]
+    )]
+    review: Option<PathBuf>,
 
-impl Config {
-    fn default_outcome_of_output(&self, output: &process::Output) -> TestOutcome {
-        let status = output.status;
-        let stdout_utf8 = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr_utf8 = String::from_utf8_lossy(&output.stderr).to_string();
+    #[arg(
+        long,
+        help = "Before bisecting, test a few toolchains spread over the range and warn if the \
+                regression looks reverted within it"
+    )]
+    detect_nonmonotonic: bool,
 
-        debug!(
-            "status: {:?} stdout: {:?} stderr: {:?}",
-            status, stdout_utf8, stderr_utf8
-        );
+    #[arg(
+        long,
+        help = "Stop with an error if the first toolchain tested by the bisection cannot be \
+                installed, instead of skipping it as unknown"
+    )]
+    fail_fast_on_install_error: bool,
 
-        let saw_ice = stderr_utf8.contains("error: internal compiler error")
-            || stderr_utf8.contains("' has overflowed its stack")
-            || stderr_utf8.contains("error: the compiler unexpectedly panicked");
+    #[arg(long, help = "Install the given artifact")]
+    install: Option<Bound>,
 
-        let input = (self.args.regress, status.success());
-        let result = match input {
-            (RegressOn::Error, true) | (RegressOn::Success, false) => TestOutcome::Baseline,
-            (RegressOn::Error, false) | (RegressOn::Success | RegressOn::NonError, true) => {
-                TestOutcome::Regressed
-            }
-            (RegressOn::Ice, _) | (RegressOn::NonError, false) => {
-                if saw_ice {
-                    TestOutcome::Regressed
-                } else {
-                    TestOutcome::Baseline
-                }
-            }
+    #[arg(
+        long,
+        value_name = "BOUND",
+        help = "Test the given nightly date or CI commit once and print the result, then exit \
+                with 0 for the baseline, 2 for a regression and 125 if unknown",
+        conflicts_with_all = ["start", "end", "install", "commits_file"]
+    )]
+    probe_only: Option<Bound>,
+
+    #[arg(
+        long,
+        help = "Check that the bounds can be bisected, without installing anything",
+        conflicts_with = "install"
+    )]
+    check_bounds: bool,
+
+    #[arg(
+        long,
+        value_name = "BOUND",
+        help = "List the components available for the given nightly date or CI commit",
+        conflicts_with_all = ["install", "check_bounds"]
+    )]
+    list_components: Option<Bound>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "text",
+        help = "Print the configuration and bounds resolved from the arguments, then exit",
+        conflicts_with_all = ["install", "check_bounds", "list_components"]
+    )]
+    print_config: Option<PrintConfigFormat>,
+
+    #[arg(
+        long,
+        help = "Print the JSON Schema of the `--print-config=json` output, then exit",
+        conflicts_with_all = ["install", "check_bounds", "list_components", "print_config"]
+    )]
+    print_schema: bool,
+
+    #[arg(
+        long,
+        visible_alias = "cleanup-all",
+        help = "Remove the toolchains left installed by earlier runs, then exit",
+        conflicts_with_all = ["install", "check_bounds", "list_components", "print_config"]
+    )]
+    cleanup: bool,
+
+    #[arg(
+        long,
+        value_name = "COMMIT",
+        help = "Bisect the unrolled perf builds of the given rollup merge commit to find the \
+                rolled-up PR responsible, then exit",
+        conflicts_with_all = [
+            "start",
+            "end",
+            "install",
+            "check_bounds",
+            "list_components",
+            "print_config",
+            "commits_file",
+            "cleanup",
+            "probe_only",
+        ]
+    )]
+    drill_rollup: Option<String>,
+
+    #[arg(
+        long,
+        help = "With --cleanup, only list the toolchains that would be removed",
+        requires = "cleanup"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        help = "Build for --target and --baseline-target at the same time",
+        requires = "baseline_target",
+        conflicts_with_all = ["toolchain_file", "check_file"]
+    )]
+    parallel_targets: bool,
+
+    #[arg(long, help = "Force installation over existing artifacts")]
+    force_install: bool,
+
+    #[arg(
+        long,
+        help = "Experimental: let rustup install each nightly, selected by a \
+                rust-toolchain.toml written in the test directory",
+        conflicts_with_all = ["install", "list_components", "commits_file", "by_commit"]
+    )]
+    toolchain_file: bool,
+
+    #[arg(
+        long,
+        env = "CARGO_BISECT_ARTIFACT_MIRROR",
+        help = "Server to download CI artifacts from before trying the official one"
+    )]
+    artifact_mirror: Option<String>,
+
+    #[arg(
+        long,
+        help = "Do not fall back to the official server for artifacts missing from the mirror",
+        requires = "artifact_mirror"
+    )]
+    no_mirror_fallback: bool,
+
+    #[arg(
+        long,
+        value_name = "KEY:VALUE",
+        env = "CARGO_BISECT_ARTIFACT_HEADER",
+        hide_env_values = true,
+        value_parser = toolchains::parse_header,
+        help = "Header to send with the requests to --artifact-mirror, for example an \
+                `Authorization` header for a private bucket (can be repeated)",
+        requires = "artifact_mirror"
+    )]
+    artifact_header: Vec<(HeaderName, HeaderValue)>,
+
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = toolchains::DEFAULT_CI_URL_TEMPLATE,
+        value_parser = validate_ci_url_template,
+        help = "URL of the CI artifacts, without the `.tar.xz` extension; {server} is replaced by \
+                the server, {alt} by `-alt` with --alt, {commit} by the commit and {component} \
+                by the name of the tarball"
+    )]
+    ci_url_template: String,
+
+    #[arg(
+        long,
+        help = "Fail instead of accessing the network, to only use toolchains that are \
+                already installed and a local rust repository",
+        conflicts_with = "toolchain_file"
+    )]
+    no_network: bool,
+
+    #[arg(
+        long,
+        help = "Download the boundary and midpoint toolchains before testing starts",
+        conflicts_with = "force_install"
+    )]
+    prefetch_boundaries: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        help = "Log every toolchain the search tests, with its index, the known ends of the \
+                range and the result, appended to the given file, or to stderr without one"
+    )]
+    trace_bisection: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "While a toolchain is tested, install the toolchains that may be tested next in \
+                the background, at most N at a time",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "toolchain_file"
+    )]
+    max_parallel_installs: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_parser = validate_dir,
+        help = "Sysroot of a rustc built from source at --start, \
+                tested in place of the start of a commit range",
+        conflicts_with = "toolchain_file"
+    )]
+    local_rustc: Option<PathBuf>,
+
+    #[arg(long, help = "Script replacement for `cargo build` command")]
+    script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Script run once per toolchain before testing it; \
+                if it fails, the toolchain is skipped as unknown"
+    )]
+    setup_script: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Interpreter to run the script with [default: run the script directly, \
+except `.sh` and `.ps1` scripts on Windows]",
+        requires = "script"
+    )]
+    shell: Option<String>,
+
+    #[arg(long, help = "Do not install cargo [default: install cargo]")]
+    without_cargo: bool,
+
+    #[arg(
+        long,
+        alias = "only-rustc",
+        help = "Only install rustc and the host standard library, and test by running \
+rustc directly with the arguments after `--`",
+        conflicts_with_all = ["with_src", "with_dev", "components", "target"]
+    )]
+    minimal: bool,
+
+    #[arg(
+        long,
+        help = "After finding the regressed commit, print the number, title, author and \
+                reviewer of its PR"
+    )]
+    confirm_culprit_pr: bool,
+
+    #[arg(
+        long,
+        help = "Print the result as a single line to stdout, such as \
+                `Regressed in 0123abcd (PR #12345): title`, sending the test output to stderr"
+    )]
+    oneline: bool,
+
+    #[arg(
+        long,
+        value_name = "LOGIN",
+        help = "Only bisect the commits merging PRs by this GitHub user, and rollups, which \
+                may include them (can be repeated)"
+    )]
+    pr_author: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "With --minimal, compile with this edition of Rust",
+        requires = "minimal",
+        conflicts_with = "script"
+    )]
+    edition: Option<Edition>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Bisect a rustup tool: installs its component, runs it by default, and \
+detects its crashes",
+        conflicts_with_all = ["minimal", "without_cargo"]
+    )]
+    tool: Option<Tool>,
+
+    #[arg(
+        long,
+        help = "Text shown when a test does match the condition requested"
+    )]
+    term_new: Option<String>,
+
+    #[arg(
+        long,
+        help = "Text shown when a test fails to match the condition requested"
+    )]
+    term_old: Option<String>,
+}
+
+pub type GitDate = NaiveDate;
+
+pub fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// Parses the contents of a `--commits-file`: the first word of every line,
+/// skipping blank lines and `#` comments. This accepts `git log --oneline`
+/// output as well as plain lists of SHAs.
+fn parse_commits_file(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect()
+}
+
+/// Whether `commit` is a full 40-digit SHA, rather than an abbreviated one or
+/// some other ref that needs to be resolved first.
+fn is_full_sha(commit: &str) -> bool {
+    commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Reads a `--report-template`, checking its placeholders up front.
+fn read_report_template(path: &Path) -> anyhow::Result<String> {
+    let template = fs::read_to_string(path)
+        .with_context(|| format!("failed to read report template {}", path.display()))?;
+    report::check(&template)
+        .map_err(|err| anyhow::anyhow!("invalid report template {}: {err}", path.display()))?;
+    Ok(template)
+}
+
+/// Checks that `--toolchain-file`, if given, can be used: only nightlies can be
+/// installed by rustup, and an existing toolchain file must not be clobbered.
+fn check_toolchain_file(args: &Opts, bounds: &Bounds) -> anyhow::Result<()> {
+    if !args.toolchain_file {
+        return Ok(());
+    }
+    if matches!(bounds, Bounds::Commits { .. }) {
+        bail!("--toolchain-file can only bisect nightlies, since rustup cannot install CI builds");
+    }
+    for name in ["rust-toolchain", "rust-toolchain.toml"] {
+        let path = args.test_dir.join(name);
+        if path.exists() {
+            bail!(
+                "--toolchain-file would overwrite `{}`; move it out of the way first",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Warns about the environment settings that can make the tests ignore the
+/// toolchains being bisected, or build something else than intended.
+fn check_environment(args: &Opts) {
+    let warning = "WARNING:".yellow().bold();
+    if !args.toolchain_file {
+        if let Some((path, pinned)) = find_pinned_toolchain(&args.test_dir) {
+            eprintln!(
+                "{warning} `{}` pins the toolchain `{pinned}`. Each toolchain is tested with \
+                 `RUSTUP_TOOLCHAIN` or `cargo +toolchain`, which take precedence over it, but \
+                 anything in the test that calls rustup itself or clears `RUSTUP_TOOLCHAIN` \
+                 would use `{pinned}` instead.",
+                path.display()
+            );
+        }
+    }
+    if args.target.is_none() {
+        if let Ok(target) = env::var("CARGO_BUILD_TARGET") {
+            eprintln!(
+                "{warning} CARGO_BUILD_TARGET={target} is set, but only the standard library \
+                 for {} is installed; pass --target={target} to bisect that target",
+                args.host
+            );
+        }
+    }
+    for var in [
+        "RUSTFLAGS",
+        "CARGO_ENCODED_RUSTFLAGS",
+        "CARGO_BUILD_RUSTFLAGS",
+    ] {
+        if let Some(value) = env::var_os(var).filter(|value| !value.is_empty()) {
+            eprintln!(
+                "note: {var}={} is set in the environment and applies to every test",
+                value.to_string_lossy()
+            );
+        }
+    }
+}
+
+/// Finds the `rust-toolchain` or `rust-toolchain.toml` file that rustup would
+/// use in `dir`, looking in its parents as rustup does, and the toolchain it
+/// pins.
+fn find_pinned_toolchain(dir: &Path) -> Option<(PathBuf, String)> {
+    let dir = dir.canonicalize().ok()?;
+    for dir in dir.ancestors() {
+        for name in ["rust-toolchain", "rust-toolchain.toml"] {
+            let path = dir.join(name);
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            return pinned_toolchain(&contents).map(|pinned| (path, pinned));
+        }
+    }
+    None
+}
+
+/// The toolchain pinned by the contents of a toolchain file: the `channel` or
+/// `path` of its `[toolchain]` table, or the whole of a legacy one-line file.
+fn pinned_toolchain(contents: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct ToolchainFile {
+        toolchain: ToolchainSection,
+    }
+    #[derive(Deserialize)]
+    struct ToolchainSection {
+        channel: Option<String>,
+        path: Option<String>,
+    }
+
+    let contents = contents.trim();
+    if let Ok(file) = toml::from_str::<ToolchainFile>(contents) {
+        return file.toolchain.channel.or(file.toolchain.path);
+    }
+    if !contents.is_empty() && !contents.contains(['\n', '[', '=']) {
+        return Some(contents.to_string());
+    }
+    None
+}
+
+/// Checks that a custom `--install-dir` looks like rustup's `toolchains`
+/// directory, since its parent is used as `RUSTUP_HOME` to run the toolchains.
+fn check_toolchains_dir(dir: &Path) -> anyhow::Result<()> {
+    if dir.file_name() != Some("toolchains".as_ref()) {
+        bail!(
+            "--install-dir `{}` must be a directory named `toolchains`, \
+             so that its parent can be used as RUSTUP_HOME",
+            dir.display()
+        );
+    }
+    let Some(parent) = dir.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        bail!(
+            "--install-dir `{}` must have a parent directory to use as RUSTUP_HOME",
+            dir.display()
+        );
+    };
+    debug!("using {} as RUSTUP_HOME", parent.display());
+    Ok(())
+}
+
+/// Whether two paths are on the same filesystem, or `None` if it can't be
+/// told.
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Some(fs::metadata(a).ok()?.dev() == fs::metadata(b).ok()?.dev())
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> Option<bool> {
+    None
+}
+
+impl Opts {
+    fn emit_cargo_output(&self) -> bool {
+        self.verbosity >= 2
+    }
+
+    fn emit_cmd(&self) -> bool {
+        self.verbosity >= 1
+    }
+
+    /// Whether the change looked for is a fix rather than a regression: the
+    /// `--regress` mode looks for one, or `--reverse` looks for where a
+    /// regression goes away.
+    fn finds_fix(&self) -> bool {
+        self.regress.finds_fix() != self.reverse
+    }
+
+    /// The change looked for: "regression" or "fix".
+    fn change_noun(&self) -> &'static str {
+        if self.finds_fix() {
+            "fix"
+        } else {
+            "regression"
+        }
+    }
+
+    /// The start of the line announcing where the change was found, as in
+    /// "Regression in" or "Fixed in".
+    fn change_heading(&self) -> &'static str {
+        if self.finds_fix() {
+            "Fixed"
+        } else {
+            "Regression"
+        }
+    }
+
+    /// The start of `sha`, `--sha-length` characters long or 8 by default.
+    fn short_sha<'a>(&self, sha: &'a str) -> &'a str {
+        let len = self.sha_length.map_or(8, usize::from);
+        sha.get(..len).unwrap_or(sha)
+    }
+
+    /// The program run by the test when no `--script` is given.
+    fn test_tool(&self) -> &'static str {
+        if self.minimal {
+            "rustc"
+        } else {
+            "cargo"
+        }
+    }
+
+    /// The arguments passed to the test tool when none are given after `--`.
+    fn default_command_args(&self) -> &'static [&'static str] {
+        match self.tool {
+            Some(tool) => tool.default_command_args(),
+            None => &["build"],
+        }
+    }
+
+    /// The arguments passed to the test tool when no `--script` is given.
+    fn test_command_args(&self) -> Vec<OsString> {
+        let args = if let Some(name) = &self.test_name {
+            libtest_messages::cargo_test_args(name)
+        } else if self.command_args.is_empty() {
+            self.default_command_args()
+                .iter()
+                .map(OsString::from)
+                .collect()
+        } else {
+            self.command_args.clone()
+        };
+        let mut args = if self.regress_crate.is_some() || self.regress_lint.is_some() {
+            cargo_messages::with_json_message_format(&args)
+        } else {
+            args
+        };
+        if let Some(edition) = self.edition {
+            args.push(format!("--edition={}", edition.as_str()).into());
+        }
+        args
+    }
+
+    /// Whether the test command builds its own standard library with
+    /// `-Zbuild-std`.
+    fn builds_std(&self) -> bool {
+        self.test_command_args()
+            .iter()
+            .any(|arg| arg.to_string_lossy().starts_with("-Zbuild-std"))
+    }
+
+    fn must_capture_output(&self) -> bool {
+        self.regress_crate.is_some()
+            || self.regress_lint.is_some()
+            || self.test_name.is_some()
+            || self.compare_tool.is_some()
+            || self.log_dir.is_some()
+            || self.regress.must_process_stderr()
+            || matches!(self.ice_is, Some(IceIs::Regression | IceIs::Baseline))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+struct ExitError(i32);
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exiting with {}", self.0)
+    }
+}
+
+impl Config {
+    /// The targets to install the standard library for, see [`std_targets`].
+    fn std_targets(&self) -> Vec<String> {
+        std_targets(&self.args, &self.target)
+    }
+
+    /// The directory to test `toolchain` in: its own copy of `--test-dir` with
+    /// `--isolate`, or `--test-dir` itself.
+    pub(crate) fn test_dir(&self, toolchain: &Toolchain) -> PathBuf {
+        match &self.isolate_dir {
+            Some(dir) => dir.path().join(toolchain.rustup_name()),
+            None => self.args.test_dir.clone(),
+        }
+    }
+
+    /// Removes the copy of the test directory made for `toolchain` by
+    /// `--isolate`, unless `--preserve` is given.
+    fn remove_isolated_test_dir(&self, toolchain: &Toolchain) {
+        let dir = self.test_dir(toolchain);
+        if self.isolate_dir.is_none() || !dir.exists() {
+            return;
+        }
+        if self.args.preserve {
+            eprintln!(
+                "preserving the test directory of {toolchain} in {}",
+                dir.display()
+            );
+        } else if let Err(err) = fs::remove_dir_all(&dir) {
+            debug!("failed to remove {}: {err}", dir.display());
+        }
+    }
+
+    /// The `RUSTUP_HOME` to run the toolchains with, when `--install-dir`
+    /// installs them outside the default one.
+    pub(crate) fn custom_rustup_home(&self) -> Option<&Path> {
+        self.args.install_dir.as_deref().and_then(Path::parent)
+    }
+
+    fn default_outcome_of_output(
+        &self,
+        toolchain: &Toolchain,
+        output: &process::Output,
+        elapsed: std::time::Duration,
+    ) -> TestOutcome {
+        let status = output.status;
+        let stdout_utf8 = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr_utf8 = String::from_utf8_lossy(&output.stderr).to_string();
+
+        debug!(
+            "status: {:?} stdout: {:?} stderr: {:?}",
+            status, stdout_utf8, stderr_utf8
+        );
+
+        let ice_marker = self.ice_marker(&stderr_utf8);
+        let saw_ice = ice_marker.is_some();
+
+        let (success, saw_ice) = match self.args.regress_crate {
+            Some(ref name) => {
+                let report = cargo_messages::crate_report(&stdout_utf8, name);
+                if !report.built && report.errors == 0 && !status.success() {
+                    eprintln!(
+                        "warning: crate `{name}` was not built, another crate may have failed first"
+                    );
+                }
+                (report.errors == 0, report.ice)
+            }
+            None => (status.success(), saw_ice),
+        };
+        let success = match &self.args.test_name {
+            Some(name) => libtest_messages::test_passed(&stdout_utf8, name).unwrap_or_else(|| {
+                eprintln!("warning: test `{name}` did not run, judging by the exit status");
+                success
+            }),
+            None => success,
+        };
+        // An explicit `--ice-is` takes precedence over the ICE handling baked
+        // into the `--regress` modes.
+        let saw_ice = saw_ice && self.args.ice_is != Some(IceIs::Ignore);
+        let input = (self.args.regress, success);
+        let (result, rule) = match input {
+            _ if saw_ice && self.args.ice_is == Some(IceIs::Regression) => {
+                (TestOutcome::Regressed, "--ice-is=regression with an ICE")
+            }
+            _ if saw_ice && self.args.ice_is == Some(IceIs::Baseline) => {
+                (TestOutcome::Baseline, "--ice-is=baseline with an ICE")
+            }
+            _ if self.args.check_file.is_some() => {
+                (self.check_file_outcome(toolchain), "--check-file")
+            }
+            _ if self.args.regress_lint.is_some() => {
+                (self.lint_outcome(&stdout_utf8), "--regress-lint")
+            }
+            _ if self.args.compare_tool.is_some() => (
+                self.compare_tool_outcome(toolchain, output),
+                "--compare-tool",
+            ),
+            (RegressOn::Slower, _) => {
+                // `Config::from_args` guarantees the threshold is set in this mode.
+                let max = self.args.max_build_seconds.unwrap_or(f64::INFINITY);
+                if elapsed.as_secs_f64() > max {
+                    (TestOutcome::Regressed, "slower than --max-build-seconds")
+                } else {
+                    (TestOutcome::Baseline, "within --max-build-seconds")
+                }
+            }
+            (RegressOn::Error, true) => (TestOutcome::Baseline, "success is the baseline"),
+            (RegressOn::Success, false) => (TestOutcome::Baseline, "failure is the baseline"),
+            (RegressOn::Error, false) => (TestOutcome::Regressed, "failure is a regression"),
+            (RegressOn::Success | RegressOn::NonError, true) => {
+                (TestOutcome::Regressed, "success is a regression")
+            }
+            (RegressOn::Ice, _) | (RegressOn::NonError, false) => {
+                if saw_ice {
+                    (TestOutcome::Regressed, "an ICE is a regression")
+                } else {
+                    (TestOutcome::Baseline, "no ICE is the baseline")
+                }
+            }
             (RegressOn::NonIce, _) => {
                 if saw_ice {
-                    TestOutcome::Baseline
+                    (TestOutcome::Baseline, "an ICE is the baseline")
                 } else {
-                    TestOutcome::Regressed
+                    (TestOutcome::Regressed, "no ICE is a regression")
+                }
+            }
+        };
+        debug!(
+            "default_outcome_of_output: input: {:?} result: {:?}",
+            input, result
+        );
+        if self.args.explain {
+            eprintln!(
+                "explain: {toolchain}: {status}, test {}, ICE: {}, took {:.1}s",
+                if success { "succeeded" } else { "failed" },
+                match (ice_marker, saw_ice) {
+                    (Some(marker), true) => format!("found `{marker}`"),
+                    (Some(marker), false) => format!("found `{marker}`, ignored"),
+                    (None, _) => "none".to_string(),
+                },
+                elapsed.as_secs_f64(),
+            );
+            eprintln!(
+                "explain: {toolchain}: --regress={}: {rule} => {result:?}",
+                self.args.regress.to_possible_value().unwrap().get_name()
+            );
+        }
+        result
+    }
+
+    /// The first marker of a crashed compiler found in `stderr`.
+    fn ice_marker(&self, stderr: &str) -> Option<&'static str> {
+        const ICE_MARKERS: &[&str] = &[
+            "error: internal compiler error",
+            "' has overflowed its stack",
+            "error: the compiler unexpectedly panicked",
+        ];
+        let tool_markers = self.args.tool.map_or(&[][..], Tool::crash_markers);
+        ICE_MARKERS
+            .iter()
+            .chain(tool_markers)
+            .find(|marker| stderr.contains(*marker))
+            .copied()
+    }
+
+    /// The outcome for `--compare-tool`, which is given the test's output in
+    /// files, and its exit code and toolchain, in environment variables.
+    fn compare_tool_outcome(&self, toolchain: &Toolchain, output: &process::Output) -> TestOutcome {
+        let Some(tool) = &self.args.compare_tool else {
+            return TestOutcome::Unknown;
+        };
+        let result = self.run_compare_tool(tool, toolchain, output);
+        match result {
+            Ok(status) => {
+                let outcome = outcome_of_compare_tool_code(status.code());
+                if outcome == TestOutcome::Unknown {
+                    eprintln!(
+                        "{} --compare-tool exited with {status}, the outcome is unknown",
+                        "WARNING:".yellow().bold()
+                    );
                 }
+                outcome
+            }
+            Err(err) => {
+                eprintln!(
+                    "{} failed to run --compare-tool `{}`: {err}",
+                    "WARNING:".yellow().bold(),
+                    tool.display()
+                );
+                TestOutcome::Unknown
             }
+        }
+    }
+
+    /// Runs `--compare-tool` on the output of a test, in a temporary directory
+    /// that is removed afterwards.
+    fn run_compare_tool(
+        &self,
+        tool: &Path,
+        toolchain: &Toolchain,
+        output: &process::Output,
+    ) -> io::Result<process::ExitStatus> {
+        let dir = tempfile::Builder::new()
+            .prefix("cargo-bisect-compare")
+            .tempdir()?;
+        let stdout = dir.path().join("stdout");
+        let stderr = dir.path().join("stderr");
+        fs::write(&stdout, &output.stdout)?;
+        fs::write(&stderr, &output.stderr)?;
+        let exit_code = output
+            .status
+            .code()
+            .map_or_else(String::new, |code| code.to_string());
+        let mut cmd = Command::new(tool);
+        cmd.current_dir(self.test_dir(toolchain))
+            .env("CARGO_BISECT_TOOLCHAIN", toolchain.rustup_name())
+            .env("CARGO_BISECT_STDOUT", &stdout)
+            .env("CARGO_BISECT_STDERR", &stderr)
+            .env("CARGO_BISECT_EXIT_CODE", exit_code)
+            .stdin(Stdio::null());
+        if self.args.emit_cmd() {
+            eprintln!("Running `{cmd:?}`");
+        }
+        cmd.status()
+    }
+
+    /// The outcome for `--regress-lint`: regressed if the lint fired.
+    fn lint_outcome(&self, stdout: &str) -> TestOutcome {
+        let Some(lint) = &self.args.regress_lint else {
+            return TestOutcome::Baseline;
         };
-        debug!(
-            "default_outcome_of_output: input: {:?} result: {:?}",
-            input, result
-        );
-        result
+        if cargo_messages::lint_fired(stdout, lint, self.args.regress_crate.as_deref()) {
+            eprintln!("lint {lint} fired");
+            TestOutcome::Regressed
+        } else {
+            TestOutcome::Baseline
+        }
+    }
+
+    /// The `--check-file` for `toolchain`, if any.
+    fn check_file_path(&self, toolchain: &Toolchain) -> Option<PathBuf> {
+        let path = self.args.check_file.as_ref()?.to_string_lossy();
+        let path = path.replace("{toolchain}", &toolchain.rustup_name());
+        Some(self.test_dir(toolchain).join(path))
+    }
+
+    /// Removes a `--check-file` left behind by an earlier test, so that it
+    /// cannot be mistaken for the output of the next one.
+    fn remove_check_file(&self, toolchain: &Toolchain) {
+        let Some(path) = self.check_file_path(toolchain) else {
+            return;
+        };
+        if let Err(err) = fs::remove_file(&path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                eprintln!("warning: failed to remove {}: {err}", path.display());
+            }
+        }
     }
+
+    /// The outcome of a test given by `--check-file`: the baseline if the
+    /// file exists and contains `--check-file-contains`, or the reverse with
+    /// `--check-file-invert`.
+    fn check_file_outcome(&self, toolchain: &Toolchain) -> TestOutcome {
+        let Some(path) = self.check_file_path(toolchain) else {
+            return TestOutcome::Baseline;
+        };
+        let matched = match (fs::read(&path), &self.args.check_file_contains) {
+            (Ok(contents), Some(needle)) => String::from_utf8_lossy(&contents).contains(needle),
+            (Ok(_), None) => true,
+            (Err(_), _) => false,
+        };
+        debug!("check file {} matched: {matched}", path.display());
+        if matched == self.args.check_file_invert {
+            TestOutcome::Regressed
+        } else {
+            TestOutcome::Baseline
+        }
+    }
+}
+
+/// Reads the exit code of `--compare-tool`: 0 is the baseline, 1 is a
+/// regression, and anything else, including being killed by a signal, means
+/// the tool could not tell.
+fn outcome_of_compare_tool_code(code: Option<i32>) -> TestOutcome {
+    match code {
+        Some(0) => TestOutcome::Baseline,
+        Some(1) => TestOutcome::Regressed,
+        _ => TestOutcome::Unknown,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// Select how the bisection is performed.
+enum BisectBy {
+    /// Infers the kind of bisection from the type of the bounds: dates bisect
+    /// nightlies (then CI builds between the two nightlies), commits bisect
+    /// CI builds. `--by-commit` turns date bounds into commits.
+    Auto,
+
+    /// Bisects nightlies. Commit bounds are converted to the date of the
+    /// commit.
+    Date,
+
+    /// Bisects CI builds. Date bounds are converted to the commit of the
+    /// nightly of that date.
+    Commit,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// The edition passed to rustc with `--edition`.
+enum Edition {
+    #[value(name = "2015")]
+    E2015,
+    #[value(name = "2018")]
+    E2018,
+    #[value(name = "2021")]
+    E2021,
+    #[value(name = "2024")]
+    E2024,
+}
+
+impl Edition {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::E2015 => "2015",
+            Self::E2018 => "2018",
+            Self::E2021 => "2021",
+            Self::E2024 => "2024",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// The format of `--print-config`.
+enum PrintConfigFormat {
+    /// One `name: value` line per setting.
+    Text,
+    /// A JSON object.
+    Json,
+}
+
+/// The version of the `--print-config=json` format. It is bumped whenever a
+/// field is removed or changes meaning, but not when one is added.
+const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// What `--print-config` prints. The schema printed by `--print-schema` is
+/// derived from this, so the field docs end up in it.
+#[derive(Serialize, JsonSchema)]
+struct ResolvedConfig {
+    /// The version of this format, bumped on incompatible changes.
+    schema_version: u32,
+    /// The host triple.
+    host: String,
+    /// The triple the test is run for.
+    target: String,
+    /// The targets the standard library is installed for.
+    std_targets: Vec<String>,
+    /// The components installed in every toolchain.
+    components: Vec<String>,
+    /// Where the toolchains are installed.
+    toolchains_path: PathBuf,
+    /// Where the downloads are unpacked.
+    tmp_path: PathBuf,
+    /// The directory the test is run in.
+    test_dir: PathBuf,
+    /// How artifacts are fetched.
+    access: Option<String>,
+    /// What is considered a regression.
+    regress: Option<String>,
+    /// The kind of bounds: `commits`, `dates` or `search-nightly-backwards`.
+    bounds: String,
+    /// The start of the range, absent when searching backwards.
+    start: Option<String>,
+    /// The end of the range.
+    end: String,
 }
 
-#[derive(Clone, Debug, ValueEnum)]
+#[derive(Clone, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum Access {
     Checkout,
     Github,
@@ -294,7 +1566,8 @@ impl Access {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 /// Customize what is treated as regression.
 enum RegressOn {
     /// Marks test outcome as `Regressed` if and only if the `rustc`
@@ -333,12 +1606,133 @@ enum RegressOn {
     /// ICE: `success` assumes that ICE should be considered baseline;
     /// `non-error` assumes ICE should be considered a sign of a regression.)
     NonError,
+
+    /// Marks test outcome as `Regressed` if and only if the test command
+    /// takes longer than `--max-build-seconds` to run. The time spent
+    /// downloading and installing the toolchain is not included. This covers
+    /// the use case for when you want to bisect a compile-time regression.
+    Slower,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// A rustup tool to bisect instead of the compiler itself.
+enum Tool {
+    /// Runs `cargo miri test` by default.
+    Miri,
+    /// Runs `cargo clippy` by default.
+    Clippy,
+    /// Runs `cargo fmt --check` by default.
+    Rustfmt,
+}
+
+impl Tool {
+    /// The rustup components needed to run the tool.
+    fn components(self) -> &'static [&'static str] {
+        match self {
+            // Miri builds its own standard library, which needs the sources.
+            Tool::Miri => &["miri", "rust-src"],
+            Tool::Clippy => &["clippy"],
+            Tool::Rustfmt => &["rustfmt"],
+        }
+    }
+
+    fn default_command_args(self) -> &'static [&'static str] {
+        match self {
+            Tool::Miri => &["miri", "test"],
+            Tool::Clippy => &["clippy"],
+            Tool::Rustfmt => &["fmt", "--check"],
+        }
+    }
+
+    /// Output that indicates the tool itself crashed, in addition to the
+    /// usual ICE messages. These are treated like an ICE.
+    fn crash_markers(self) -> &'static [&'static str] {
+        match self {
+            // Miri and Clippy run inside the compiler's `rustc` thread; a
+            // panic of the interpreted program happens on other threads.
+            Tool::Miri | Tool::Clippy => &["thread 'rustc' panicked at"],
+            Tool::Rustfmt => &["thread 'main' panicked at"],
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// The built-in formats of the final report, used without `--report-template`.
+enum ReportFormat {
+    /// The nightlies and commits searched and the ones that regressed.
+    Summary,
+    /// The regression issue template of rust-lang/rust, including the summary
+    /// and a `@rustbot` command adding the regression label.
+    GithubIssue,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// Which logs of `--log-dir` are kept once the bisection is done.
+enum KeepLogs {
+    /// Keeps the logs of every toolchain tested.
+    All,
+    /// Keeps the logs of the last toolchain without the regression and of the
+    /// first one with it.
+    Boundaries,
+    /// Keeps the logs only if the bisection did not narrow the regression down
+    /// to a single toolchain.
+    OnFailure,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// How the outcomes of a toolchain tested several times with `--repeat` are
+/// combined.
+enum RepeatPolicy {
+    /// Marks the toolchain as regressed only if every run regressed.
+    All,
+    /// Marks the toolchain as regressed if more than half of the runs regressed.
+    Majority,
+}
+
+impl RepeatPolicy {
+    /// Combines the outcomes, or returns `None` if more runs are needed.
+    fn decide(self, regressed: u32, baseline: u32, total: u32) -> Option<TestOutcome> {
+        let (needed_to_regress, needed_for_baseline) = match self {
+            RepeatPolicy::All => (total, 1),
+            RepeatPolicy::Majority => (total / 2 + 1, total - total / 2),
+        };
+        if regressed >= needed_to_regress {
+            Some(TestOutcome::Regressed)
+        } else if baseline >= needed_for_baseline {
+            Some(TestOutcome::Baseline)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+/// Customize how an internal compiler error (ICE) is interpreted, independently
+/// of the `--regress` mode.
+enum IceIs {
+    /// Marks test outcome as `Regressed` whenever the `rustc` process issues
+    /// a diagnostic indicating that an ICE occurred.
+    Regression,
+
+    /// Marks test outcome as `Baseline` whenever the `rustc` process issues
+    /// a diagnostic indicating that an ICE occurred.
+    Baseline,
+
+    /// Does not treat ICEs specially; only the criterion of the `--regress`
+    /// mode applies.
+    Ignore,
 }
 
 impl RegressOn {
+    /// Whether the "regression" this mode looks for is really a fix, so that
+    /// the results should say so.
+    fn finds_fix(self) -> bool {
+        matches!(self, RegressOn::Success | RegressOn::NonIce)
+    }
+
     fn must_process_stderr(self) -> bool {
         match self {
-            RegressOn::Error | RegressOn::Success => false,
+            RegressOn::Error | RegressOn::Success | RegressOn::Slower => false,
             RegressOn::NonError | RegressOn::Ice | RegressOn::NonIce => true,
         }
     }
@@ -351,31 +1745,122 @@ struct Config {
     toolchains_path: PathBuf,
     target: String,
     client: Client,
+    /// Keeps the crate fetched with `--test-crate` alive until we exit.
+    _test_crate_dir: Option<TempDir>,
+    /// With `--isolate`, the directory holding the copies of the test
+    /// directory, one for each toolchain.
+    isolate_dir: Option<TempDir>,
+    /// The contents of `--report-template`.
+    report_template: Option<String>,
+    /// The first test runs, kept to compare two different toolchains.
+    /// See [`Config::check_compiler_dependence`].
+    first_runs: Mutex<FirstRuns>,
+    /// Toolchains tested by the bisections so far, counted against
+    /// `--max-steps`.
+    bisect_steps: AtomicUsize,
+    /// The files written to `--log-dir` so far, by toolchain name.
+    logs: Mutex<Vec<(String, PathBuf)>>,
 }
 
 impl Config {
-    fn from_args(args: Opts) -> anyhow::Result<Config> {
+    fn from_args(mut args: Opts) -> anyhow::Result<Config> {
+        if args.regress == RegressOn::Slower && args.max_build_seconds.is_none() {
+            bail!("--regress=slower requires --max-build-seconds to be specified");
+        }
+        if args.minimal && args.script.is_none() && args.command_args.is_empty() {
+            bail!(
+                "--minimal runs rustc directly, so the arguments to pass to it must be \
+                 given after `--`, for example `-- src/main.rs`"
+            );
+        }
+        check_triples(&mut args);
+        resolve_scripts(&mut args)?;
+        if args.baseline_target.is_some() && args.baseline_target == args.target {
+            bail!("--baseline-target must be different from --target");
+        }
+        if args.checkout_submodules && !matches!(args.access, Access::Checkout) {
+            bail!("--checkout-submodules requires --access=checkout");
+        }
+        if args.ice_is.is_some() && matches!(args.regress, RegressOn::Ice | RegressOn::NonIce) {
+            bail!(
+                "--ice-is cannot be combined with --regress={}",
+                args.regress.to_possible_value().unwrap().get_name()
+            );
+        }
+
+        if args.report == ReportFormat::GithubIssue && args.finds_fix() {
+            bail!("--report=github-issue files a regression, but this bisection looks for a fix");
+        }
+
         let target = args.target.clone().unwrap_or_else(|| args.host.clone());
+        warn_missing_target_std(&args, &target);
 
-        let mut toolchains_path = home::rustup_home()?;
+        let rustup_home = home::rustup_home()?;
 
         // We will download and extract the tarballs into this directory before installing.
         // Using `~/.rustup/tmp` instead of $TMPDIR ensures we could always perform installation by
         // renaming instead of copying the whole directory.
-        let rustup_tmp_path = toolchains_path.join("tmp");
-        if !rustup_tmp_path.exists() {
-            fs::create_dir(&rustup_tmp_path)?;
-        }
+        let rustup_tmp_path = match &args.tmp_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = rustup_home.join("tmp");
+                if !dir.exists() {
+                    fs::create_dir(&dir)?;
+                }
+                dir
+            }
+        };
 
-        toolchains_path.push("toolchains");
-        if !toolchains_path.is_dir() {
-            bail!(
-                "`{}` is not a directory. Please install rustup.",
+        let toolchains_path = toolchains_path(&args, &rustup_home)?;
+
+        if same_filesystem(&rustup_tmp_path, &toolchains_path) == Some(false) {
+            eprintln!(
+                "{} `{}` and `{}` are on different filesystems, \
+                 so each toolchain will be copied into place instead of moved",
+                "WARNING:".yellow().bold(),
+                rustup_tmp_path.display(),
                 toolchains_path.display()
             );
         }
 
         let bounds = Bounds::from_args(&args)?;
+        let report_template = args
+            .report_template
+            .as_deref()
+            .map(read_report_template)
+            .transpose()?;
+
+        let client = Client::new();
+
+        let test_crate_dir = match &args.test_crate {
+            Some(spec) => {
+                let dir = crates_io::fetch_crate(&client, spec, args.preserve)?;
+                args.test_dir = dir.path().join(spec.dir_name());
+                if args.preserve {
+                    eprintln!("preserving {spec} in {}", args.test_dir.display());
+                }
+                Some(dir)
+            }
+            None => None,
+        };
+
+        check_toolchain_file(&args, &bounds)?;
+        bounds::check_known_good_date(args.known_good_date, &bounds)?;
+        check_environment(&args);
+        let isolate_dir = args
+            .isolate
+            .then(|| {
+                tempfile::Builder::new()
+                    .prefix("cargo-bisect-isolate")
+                    .keep(args.preserve)
+                    .tempdir()
+            })
+            .transpose()?;
+
+        if let Some(dir) = &args.log_dir {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create --log-dir {}", dir.display()))?;
+        }
 
         Ok(Config {
             args,
@@ -383,9 +1868,42 @@ impl Config {
             target,
             toolchains_path,
             rustup_tmp_path,
-            client: Client::new(),
+            client,
+            _test_crate_dir: test_crate_dir,
+            isolate_dir,
+            report_template,
+            first_runs: Mutex::default(),
+            bisect_steps: AtomicUsize::new(0),
+            logs: Mutex::new(Vec::new()),
         })
     }
+
+    /// Warns if the first runs of two different toolchains look exactly alike.
+    ///
+    /// When both bounds are given, the first two tested toolchains are the
+    /// start and the end of the range, which are expected to behave
+    /// differently. Identical output and timing usually means the test command
+    /// does not actually use the toolchain being bisected (for example a
+    /// script that ignores `RUSTUP_TOOLCHAIN`), which makes the bisection
+    /// meaningless.
+    fn check_compiler_dependence(&self, run: TestRun) {
+        if !matches!(self.bounds, Bounds::Commits { .. } | Bounds::Dates { .. }) {
+            return;
+        }
+        let Some((first, run)) = self.first_runs.lock().unwrap().record(run) else {
+            return;
+        };
+        if first.looks_like(&run) {
+            eprintln!(
+                "{} {} and {} produced identical output in about the same time. \
+                 The test may not depend on the compiler version; make sure it uses \
+                 the toolchain selected via `RUSTUP_TOOLCHAIN` or `cargo +toolchain`.",
+                "WARNING:".yellow().bold(),
+                first.toolchain,
+                run.toolchain,
+            );
+        }
+    }
 }
 
 // Application entry point
@@ -399,83 +1917,486 @@ fn run() -> anyhow::Result<()> {
             os_args.remove(1);
         }
     }
-    let args = Opts::parse_from(os_args);
+    let matches = Opts::command().get_matches_from(os_args);
+    let mut args = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if args.print_schema {
+        let schema = schemars::schema_for!(ResolvedConfig);
+        println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        return Ok(());
+    }
+    // Only ask `rustc` for its host when it is needed, so that `--help` does
+    // not spawn a process.
+    if matches.value_source("host") != Some(ValueSource::CommandLine) {
+        args.host = default_host();
+    }
+    if let Some(path) = args.config.clone() {
+        ConfigFile::load(&path)?.apply(&mut args, &matches)?;
+    }
+    set_color_override(args.color);
+    VERBOSE_NETWORK.store(args.emit_cmd(), Ordering::Relaxed);
+    toolchains::set_sha_length(args.sha_length.map_or(0, usize::from));
+    github::set_commit_limit(args.commit_limit);
+    toolchains::set_no_network(args.no_network);
+    toolchains::set_artifact_headers(&args);
+    if let Some(path) = &args.review {
+        return review::review(path, args.bias);
+    }
+    if args.cleanup {
+        return cleanup(&args);
+    }
     let cfg = Config::from_args(args)?;
 
     if let Some(ref bound) = cfg.args.install {
         cfg.install(bound)
+    } else if cfg.args.check_bounds {
+        cfg.check_bounds()
+    } else if let Some(ref bound) = cfg.args.list_components {
+        cfg.list_components(bound)
+    } else if let Some(ref bound) = cfg.args.probe_only {
+        cfg.probe_only(bound)
+    } else if let Some(ref commit) = cfg.args.drill_rollup {
+        cfg.drill_rollup(commit)
+    } else if let Some(format) = cfg.args.print_config {
+        cfg.print_config(format);
+        Ok(())
     } else {
         cfg.bisect()
     }
 }
 
+/// The directory rustup keeps its toolchains in, or the `--install-dir`.
+fn toolchains_path(args: &Opts, rustup_home: &Path) -> anyhow::Result<PathBuf> {
+    match &args.install_dir {
+        Some(dir) => {
+            check_toolchains_dir(dir)?;
+            Ok(dir.clone())
+        }
+        None => {
+            let dir = rustup_home.join("toolchains");
+            if !dir.is_dir() {
+                bail!(
+                    "`{}` is not a directory. Please install rustup.",
+                    dir.display()
+                );
+            }
+            Ok(dir)
+        }
+    }
+}
+
+/// Removes the toolchains installed by this tool that are left in the
+/// toolchains directory, for `--cleanup`.
+fn cleanup(args: &Opts) -> anyhow::Result<()> {
+    let toolchains_path = toolchains_path(args, &home::rustup_home()?)?;
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&toolchains_path)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if toolchains::is_bisector_toolchain(&name) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    if names.is_empty() {
+        eprintln!("no toolchains to remove in {}", toolchains_path.display());
+        return Ok(());
+    }
+    let mut total = 0;
+    for name in names {
+        let dir = toolchains_path.join(&name);
+        let size = toolchains::dir_size(&dir).unwrap_or(0);
+        if args.dry_run {
+            eprintln!("would remove {name} ({} MiB)", size / (1024 * 1024));
+        } else {
+            eprintln!("removing {name} ({} MiB)", size / (1024 * 1024));
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("failed to remove {}", dir.display()))?;
+        }
+        total += size;
+    }
+    let total = total / (1024 * 1024);
+    if args.dry_run {
+        eprintln!("{total} MiB would be reclaimed");
+    } else {
+        eprintln!("reclaimed {total} MiB");
+    }
+    Ok(())
+}
+
+/// The host triple of the installed `rustc`, falling back to the triple this
+/// binary was built for if `rustc` cannot be run.
+fn default_host() -> String {
+    rustc_version::version_meta().map_or_else(|_| env!("HOST").to_string(), |meta| meta.host)
+}
+
+/// The targets to install the standard library for: the host, the
+/// `--target` unless `--no-target-std` or `--no-prebuilt-std` is given, and
+/// the `--baseline-target`. Custom target specifications have no prebuilt
+/// standard library.
+fn std_targets(args: &Opts, target: &str) -> Vec<String> {
+    let mut targets = vec![args.host.clone()];
+    if !args.no_target_std && !args.no_prebuilt_std {
+        targets.push(target.to_string());
+    }
+    targets.extend(args.baseline_target.clone());
+    targets.retain(|target| !is_target_spec(target));
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// Warns if `--no-target-std` or `--no-prebuilt-std` leave the test without
+/// the standard library it needs.
+fn warn_missing_target_std(args: &Opts, target: &str) {
+    if args.script.is_some() || args.builds_std() {
+        return;
+    }
+    let flag = if args.no_target_std {
+        "--no-target-std"
+    } else if args.no_prebuilt_std {
+        "--no-prebuilt-std"
+    } else {
+        return;
+    };
+    eprintln!(
+        "{} {flag} skips the standard library for {target}, \
+         which the test command needs unless it uses -Zbuild-std",
+        "WARNING:".yellow().bold()
+    );
+}
+
+/// Makes the paths of the scripts run during tests absolute, since they are
+/// run from `--test-dir` rather than the current directory.
+fn resolve_scripts(args: &mut Opts) -> anyhow::Result<()> {
+    let cwd = env::current_dir()?;
+    for script in [
+        &mut args.script,
+        &mut args.setup_script,
+        &mut args.compare_tool,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *script = toolchains::resolve_script(&cwd, script);
+    }
+    Ok(())
+}
+
+/// Checks `--host`, `--target` and `--baseline-target` against the targets
+/// known to the installed `rustc`, so that a misspelled triple is pointed out
+/// before anything is downloaded. A triple that only lacks the vendor, or has
+/// the wrong one, is corrected.
+fn check_triples(args: &mut Opts) {
+    let Some(known) = known_targets() else {
+        return;
+    };
+    let triples = [
+        ("--host", Some(&mut args.host)),
+        ("--target", args.target.as_mut()),
+        ("--baseline-target", args.baseline_target.as_mut()),
+    ];
+    for (option, triple) in triples {
+        let Some(triple) = triple.filter(|triple| !is_target_spec(triple)) else {
+            continue;
+        };
+        match check_known_triple(triple, &known) {
+            TripleCheck::Known => {}
+            TripleCheck::Corrected(correction) => {
+                eprintln!("{option}: using `{correction}` for `{triple}`");
+                *triple = correction;
+            }
+            // Older nightlies can ship targets that have since been renamed,
+            // such as `wasm32-wasi`, so this is only a hint.
+            TripleCheck::NearMiss(suggestion) => eprintln!(
+                "{} {option} `{triple}` is not a target known to the installed rustc; \
+                 did you mean `{suggestion}`?",
+                "WARNING:".yellow().bold()
+            ),
+            TripleCheck::Unknown => eprintln!(
+                "{} {option} `{triple}` is not a target known to the installed rustc",
+                "WARNING:".yellow().bold()
+            ),
+        }
+    }
+}
+
+/// The target triples known to the installed `rustc`, if it can be run.
+fn known_targets() -> Option<Vec<String>> {
+    let output = Command::new("rustc")
+        .args(["--print", "target-list"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let known: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect();
+    (!known.is_empty()).then_some(known)
+}
+
+/// Whether `network_log!` prints, set from `--verbose`.
+static VERBOSE_NETWORK: AtomicBool = AtomicBool::new(false);
+
+fn verbose_network() -> bool {
+    VERBOSE_NETWORK.load(Ordering::Relaxed)
+}
+
+/// Configures the global `colored` override used by every colored call site.
+///
+/// In `auto` mode, `colored` already honors `NO_COLOR`, `CLICOLOR` and
+/// `CLICOLOR_FORCE`, but it only checks whether stdout is a terminal, while
+/// nearly all of our output goes to stderr.
+fn set_color_override(choice: ColorChoice) {
+    match choice {
+        ColorChoice::Always => colored::control::set_override(true),
+        ColorChoice::Never => colored::control::set_override(false),
+        ColorChoice::Auto => {
+            if !io::stderr().is_terminal() && env::var_os("CLICOLOR_FORCE").is_none() {
+                colored::control::set_override(false);
+            }
+        }
+    }
+}
+
 impl Config {
     fn install(&self, bound: &Bound) -> anyhow::Result<()> {
-        match *bound {
+        let (t, dl_params) = self.toolchain_of_bound(bound)?;
+        t.install(&self.client, &dl_params)?;
+        report_installed(&t, &dl_params);
+        verify_installed(&t, &dl_params);
+        Ok(())
+    }
+
+    /// The toolchain of a nightly date or CI commit, and how to download it.
+    fn toolchain_of_bound(&self, bound: &Bound) -> anyhow::Result<(Toolchain, DownloadParams)> {
+        Ok(match *bound {
             Bound::Commit(ref sha) => {
                 let sha = self.args.access.repo().commit(sha)?.sha;
-                let mut t = Toolchain {
-                    spec: ToolchainSpec::Ci {
-                        commit: sha,
-                        alt: self.args.alt,
-                    },
-                    host: self.args.host.clone(),
-                    std_targets: vec![self.args.host.clone(), self.target.clone()],
-                };
-                t.std_targets.sort();
-                t.std_targets.dedup();
-                let dl_params = DownloadParams::for_ci(self);
-                t.install(&self.client, &dl_params)?;
+                (self.ci_toolchain(sha), DownloadParams::for_ci(self))
             }
             Bound::Date(date) => {
-                let mut t = Toolchain {
+                let t = Toolchain {
                     spec: ToolchainSpec::Nightly { date },
                     host: self.args.host.clone(),
-                    std_targets: vec![self.args.host.clone(), self.target.clone()],
+                    std_targets: self.std_targets(),
                 };
-                t.std_targets.sort();
-                t.std_targets.dedup();
-                let dl_params = DownloadParams::for_nightly(self);
-                t.install(&self.client, &dl_params)?;
+                (t, DownloadParams::for_nightly(self))
             }
+        })
+    }
+
+    /// Tests a single toolchain for `--probe-only`, exiting with a code that
+    /// tells the result.
+    fn probe_only(&self, bound: &Bound) -> anyhow::Result<()> {
+        let (t, dl_params) = self.toolchain_of_bound(bound)?;
+        let code = match self.install_and_test(&t, &dl_params)? {
+            Satisfies::No => 0,
+            Satisfies::Yes => 2,
+            Satisfies::Unknown => 125,
+        };
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(ExitError(code).into())
         }
+    }
 
+    /// Prints the components available for `bound` on the host, for
+    /// `--list-components`.
+    fn list_components(&self, bound: &Bound) -> anyhow::Result<()> {
+        let host = &self.args.host;
+        let (what, components) = match *bound {
+            Bound::Commit(ref sha) => {
+                let sha = self.args.access.repo().commit(sha)?.sha;
+                let components = ci_components(&self.client, &self.args, &sha)?;
+                (format!("CI commit {sha}"), components)
+            }
+            Bound::Date(date) => {
+                let components = nightly_components(&self.client, date, host)?;
+                (format!("nightly-{}", date.format(YYYY_MM_DD)), components)
+            }
+        };
+        if components.is_empty() {
+            bail!("no components found for {what} on {host}");
+        }
+        eprintln!("components available for {what} on {host}:");
+        for component in components {
+            println!("{component}");
+        }
         Ok(())
     }
 
-    fn do_perf_search(&self, result: &BisectionResult) {
+    /// With `--confirm-culprit-pr`, prints the PR merged in the regressed
+    /// commit. Rollups are left to `do_perf_search`.
+    fn confirm_culprit_pr(&self, result: &BisectionResult) {
+        if !self.args.confirm_culprit_pr {
+            return;
+        }
+        let ToolchainSpec::Ci { commit, .. } = &result.searched[result.found].spec else {
+            return;
+        };
+        let summary = match self.args.access.repo().commit(commit) {
+            Ok(commit) => commit.summary,
+            Err(e) => {
+                eprintln!("ERROR: failed to get the PR of {commit}: {e}");
+                return;
+            }
+        };
+        let Some(pr) = CulpritPr::parse(&summary) else {
+            return;
+        };
+        eprintln!(
+            "The {} was introduced by PR #{}{}",
+            self.args.change_noun(),
+            pr.number,
+            pr.title.map_or(String::new(), |title| format!(": {title}"))
+        );
+        eprintln!("  author: @{}, reviewer: @{}", pr.author, pr.reviewer);
+        eprintln!("  https://github.com/rust-lang/rust/pull/{}", pr.number);
+    }
+
+    fn do_perf_search(&self, result: &BisectionResult) -> Option<PerfBisectionResult> {
         let toolchain = &result.searched[result.found];
-        match self.search_perf_builds(toolchain) {
+        let ToolchainSpec::Ci { commit, .. } = &toolchain.spec else {
+            eprintln!("ERROR: not a ci commit");
+            return None;
+        };
+        match self.search_perf_builds(commit) {
             Ok(result) => {
-                let bisection = result.bisection;
-                let url = format!(
-                    "https://github.com/rust-lang-ci/rust/commit/{}",
-                    bisection.searched[bisection.found]
-                )
-                .red()
-                .bold();
-                eprintln!("Regression in {url}");
-
-                // In case the bisected commit has been garbage-collected by github, we show its
-                // additional context here.
-                let context = &result.toolchain_descriptions[bisection.found];
-                eprintln!("The PR introducing the regression in this rollup is {context}");
+                self.print_perf_result(&result);
+                Some(result)
             }
             Err(e) => {
                 eprintln!("ERROR: {e}");
+                None
             }
         }
     }
 
+    /// Prints the result of the bisection to stdout for `--oneline`. The PR
+    /// of a rollup is the one `perf` found among its unrolled builds, if any.
+    fn print_oneline(&self, result: &BisectionResult, perf: Option<&PerfBisectionResult>) {
+        if !self.args.oneline {
+            return;
+        }
+        let verb = if self.args.finds_fix() {
+            "Fixed"
+        } else {
+            "Regressed"
+        };
+        let found = &result.searched[result.found];
+        if let Some(last_baseline) = result.last_baseline {
+            println!(
+                "{verb} between {} and {found}",
+                result.searched[last_baseline]
+            );
+            return;
+        }
+        let ToolchainSpec::Ci { commit, .. } = &found.spec else {
+            println!("{verb} in {found}");
+            return;
+        };
+        let pr = match perf {
+            Some(perf) => {
+                let description = &perf.toolchain_descriptions[perf.bisection.found];
+                let (number, title) = description
+                    .split_once(": ")
+                    .map_or((description.as_str(), None), |(number, title)| {
+                        (number, Some(title.to_string()))
+                    });
+                Some((number.trim_start_matches('#').to_string(), title))
+            }
+            None => match self.args.access.repo().commit(commit) {
+                Ok(commit) => CulpritPr::parse(&commit.summary)
+                    .map(|pr| (pr.number.to_string(), pr.title.map(str::to_string))),
+                Err(err) => {
+                    eprintln!(
+                        "{} failed to look up the PR of {commit}: {err}",
+                        "WARNING:".yellow().bold()
+                    );
+                    None
+                }
+            },
+        };
+        let line = oneline(verb, self.args.short_sha(commit), pr);
+        println!("{line}");
+    }
+
+    /// Bisects the unrolled perf builds of the rollup merged in `commit`, for
+    /// `--drill-rollup`.
+    fn drill_rollup(&self, commit: &str) -> anyhow::Result<()> {
+        let sha = self.args.access.repo().commit(commit)?.sha;
+        let result = self.search_perf_builds(&sha)?;
+        self.print_perf_result(&result);
+        Ok(())
+    }
+
+    fn print_perf_result(&self, result: &PerfBisectionResult) {
+        let bisection = &result.bisection;
+        let url = format!(
+            "https://github.com/rust-lang-ci/rust/commit/{:#}",
+            bisection.searched[bisection.found]
+        )
+        .red()
+        .bold();
+        eprintln!("{} in {url}", self.args.change_heading());
+
+        // In case the bisected commit has been garbage-collected by github, we show its
+        // additional context here.
+        let context = &result.toolchain_descriptions[bisection.found];
+        eprintln!(
+            "The PR introducing the {} in this rollup is {context}",
+            self.args.change_noun()
+        );
+    }
+
     // bisection entry point
     fn bisect(&self) -> anyhow::Result<()> {
-        if let Bounds::Commits { start, end } = &self.bounds {
+        if self.args.local_rustc.is_some()
+            && self.args.commits_file.is_none()
+            && !matches!(self.bounds, Bounds::Commits { .. })
+        {
+            bail!("--local-rustc replaces the start of a commit range, but the bounds are dates");
+        }
+        if let Some(path) = &self.args.commits_file {
+            let bisection_result = self.bisect_commits_file(path)?;
+            self.print_results(&bisection_result);
+            let mut perf = None;
+            if bisection_result.last_baseline.is_none() {
+                self.confirm_culprit_pr(&bisection_result);
+                perf = self.do_perf_search(&bisection_result);
+            }
+            self.print_oneline(&bisection_result, perf.as_ref());
+            self.prune_logs(&[&bisection_result]);
+        } else if let Bounds::Commits { start, end } = &self.bounds {
             let bisection_result = self.bisect_ci(start, end)?;
             self.print_results(&bisection_result);
-            self.do_perf_search(&bisection_result);
+            let mut perf = None;
+            if bisection_result.last_baseline.is_none() {
+                self.confirm_culprit_pr(&bisection_result);
+                perf = self.do_perf_search(&bisection_result);
+            }
+            self.print_oneline(&bisection_result, perf.as_ref());
+            self.prune_logs(&[&bisection_result]);
         } else {
             let nightly_bisection_result = self.bisect_nightlies()?;
             self.print_results(&nightly_bisection_result);
+            if nightly_bisection_result.last_baseline.is_some() {
+                // There is no single nightly to look for the regressed commit in.
+                self.print_oneline(&nightly_bisection_result, None);
+                self.prune_logs(&[&nightly_bisection_result]);
+                return Ok(());
+            }
+            if self.args.toolchain_file {
+                eprintln!(
+                    "not bisecting the commits of the nightly: rustup cannot install CI builds \
+                     for --toolchain-file; run again without it to narrow down the commit"
+                );
+                self.print_oneline(&nightly_bisection_result, None);
+                self.prune_logs(&[&nightly_bisection_result]);
+                return Ok(());
+            }
             let nightly_regression =
                 &nightly_bisection_result.searched[nightly_bisection_result.found];
 
@@ -499,21 +2420,206 @@ impl Config {
 
                 let bad_commit = Bound::Date(date).sha()?;
                 eprintln!(
-                    "looking for regression commit between {} and {}",
+                    "looking for {} commit between {} and {}",
+                    self.args.change_noun(),
                     previous_date.format(YYYY_MM_DD),
                     date.format(YYYY_MM_DD),
                 );
 
-                let ci_bisection_result = self.bisect_ci_via(&working_commit, &bad_commit)?;
+                // The nightly bisection has already shown that the nightly of
+                // `previous_date` passes and the one of `date` regresses, and
+                // those nightlies are built from these same commits.
+                let ci_bisection_result =
+                    self.bisect_ci_via(&working_commit, &bad_commit, BoundsStatus::Known)?;
 
                 self.print_results(&ci_bisection_result);
-                self.do_perf_search(&ci_bisection_result);
-                print_final_report(self, &nightly_bisection_result, &ci_bisection_result);
+                let mut perf = None;
+                if ci_bisection_result.last_baseline.is_none() {
+                    self.confirm_culprit_pr(&ci_bisection_result);
+                    perf = self.do_perf_search(&ci_bisection_result);
+                    print_final_report(self, &nightly_bisection_result, &ci_bisection_result);
+                }
+                self.print_oneline(&ci_bisection_result, perf.as_ref());
+                self.prune_logs(&[&nightly_bisection_result, &ci_bisection_result]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Prints what was resolved from the arguments, for `--print-config`.
+    fn print_config(&self, format: PrintConfigFormat) {
+        let name =
+            |value: Option<clap::builder::PossibleValue>| value.map(|v| v.get_name().to_string());
+        let (kind, start, end) = match &self.bounds {
+            Bounds::SearchNightlyBackwards { end } => {
+                ("search-nightly-backwards", None, end.to_string())
+            }
+            Bounds::Commits { start, end } => ("commits", Some(start.clone()), end.clone()),
+            Bounds::Dates { start, end } => ("dates", Some(start.to_string()), end.to_string()),
+        };
+        let resolved = ResolvedConfig {
+            schema_version: CONFIG_SCHEMA_VERSION,
+            host: self.args.host.clone(),
+            target: self.target.clone(),
+            std_targets: self.std_targets(),
+            components: DownloadParams::for_nightly(self).components().to_vec(),
+            toolchains_path: self.toolchains_path.clone(),
+            tmp_path: self.rustup_tmp_path.clone(),
+            test_dir: self.args.test_dir.clone(),
+            access: name(self.args.access.to_possible_value()),
+            regress: name(self.args.regress.to_possible_value()),
+            bounds: kind.to_string(),
+            start,
+            end,
+        };
+        match format {
+            PrintConfigFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&resolved).unwrap());
+            }
+            PrintConfigFormat::Text => {
+                let serde_json::Value::Object(settings) = serde_json::to_value(&resolved).unwrap()
+                else {
+                    unreachable!("`ResolvedConfig` serializes to an object");
+                };
+                for (key, value) in settings {
+                    // The version only matters to tools reading the JSON.
+                    if key == "schema_version" {
+                        continue;
+                    }
+                    let value = match value {
+                        serde_json::Value::String(s) => s,
+                        serde_json::Value::Array(items) => items
+                            .iter()
+                            .map(|item| item.as_str().unwrap_or_default().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        serde_json::Value::Null => "-".to_string(),
+                        value => value.to_string(),
+                    };
+                    println!("{key}: {value}");
+                }
             }
         }
+    }
 
+    /// Checks that the bounds resolve and have artifacts, for
+    /// `--check-bounds`.
+    fn check_bounds(&self) -> anyhow::Result<()> {
+        let mut failed = false;
+        let mut report = |what: String, result: anyhow::Result<()>| match result {
+            Ok(()) => eprintln!("{} {what}", "ok:".green().bold()),
+            Err(err) => {
+                failed = true;
+                eprintln!("{} {what}: {err}", "FAILED:".red().bold());
+            }
+        };
+        match self.bounds {
+            Bounds::Commits { ref start, ref end } => {
+                for (which, sha) in [("start", start), ("end", end)] {
+                    self.check_commit_bound(which, sha, &mut report);
+                }
+            }
+            Bounds::Dates { start, end } => {
+                for (which, date) in [("start", start), ("end", end)] {
+                    self.check_nightly_bound(which, date, &mut report);
+                }
+            }
+            Bounds::SearchNightlyBackwards { end } => {
+                self.check_nightly_bound("end", end, &mut report);
+                eprintln!("no start given, it will be searched for when bisecting");
+            }
+        }
+        if failed {
+            bail!("the bounds cannot be bisected as given");
+        }
+        eprintln!("{}", "the bounds look good".green().bold());
         Ok(())
     }
+
+    fn check_commit_bound(
+        &self,
+        which: &str,
+        sha: &str,
+        report: &mut impl FnMut(String, anyhow::Result<()>),
+    ) {
+        let commit = match self.args.access.repo().commit(sha) {
+            Ok(commit) => commit,
+            Err(err) => {
+                report(format!("{which} commit {sha} resolves"), Err(err));
+                return;
+            }
+        };
+        report(format!("{which} commit {sha} resolves"), Ok(()));
+        report(
+            format!("{which} commit is a {BORS_AUTHOR} merge"),
+            if commit.committer.name == BORS_AUTHOR {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "committed by {}; use a merge commit on the master branch",
+                    commit.committer.name
+                ))
+            },
+        );
+        let age = (today() - commit.date).num_days();
+        report(
+            format!("{which} commit is within the {CI_RETENTION_DAYS} day retention"),
+            if age < CI_RETENTION_DAYS {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("it is {age} days old"))
+            },
+        );
+        report(
+            format!("{which} commit has CI artifacts for {}", self.args.host),
+            match ci_artifacts_exist(&self.client, &self.args, &commit.sha) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(anyhow::anyhow!("not found on the server")),
+                Err(err) => Err(err.into()),
+            },
+        );
+    }
+
+    fn check_nightly_bound(
+        &self,
+        which: &str,
+        date: GitDate,
+        report: &mut impl FnMut(String, anyhow::Result<()>),
+    ) {
+        let date_str = date.format(YYYY_MM_DD);
+        report(
+            format!(
+                "{which} nightly {date_str} has artifacts for {}",
+                self.args.host
+            ),
+            match nightly_artifacts_exist(&self.client, date, &self.args.host) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(anyhow::anyhow!("not found on the server")),
+                Err(err) => Err(err.into()),
+            },
+        );
+    }
+}
+
+/// Lists the components of an `--install`ed toolchain.
+fn report_installed(toolchain: &Toolchain, dl_params: &DownloadParams) {
+    eprintln!(
+        "installed {toolchain} with {}, and rust-std for {}",
+        dl_params.components().join(", "),
+        toolchain.std_targets.join(", ")
+    );
+}
+
+/// Double-checks that an `--install`ed toolchain is what was asked for, in
+/// case the server handed out an unexpected build.
+fn verify_installed(toolchain: &Toolchain, dl_params: &DownloadParams) {
+    match toolchain.verify_installed(dl_params) {
+        Ok(()) => eprintln!("verified that {toolchain} reports the expected commit"),
+        Err(msg) => eprintln!("{} {msg}", "WARNING:".yellow().bold()),
+    }
 }
 
 fn searched_range(
@@ -551,12 +2657,43 @@ impl Config {
             searched: toolchains,
             dl_spec,
             found,
+            last_baseline,
         } = bisection_result;
 
         let (start, end) = searched_range(self, toolchains);
 
         eprintln!("searched toolchains {} through {}", start, end);
 
+        if let Some(last_baseline) = last_baseline {
+            let baseline = &toolchains[*last_baseline];
+            let regressed = &toolchains[*found];
+            eprintln!();
+            eprintln!();
+            eprintln!("{}", "*".repeat(80).dimmed().bold());
+            eprintln!(
+                "{}",
+                format!(
+                    "{} between {baseline} and {regressed}",
+                    self.args.change_heading()
+                )
+                .red()
+            );
+            eprintln!(
+                "this is a range, not a single toolchain: --max-steps stopped the bisection \
+                 before it could be narrowed down further"
+            );
+            if let (ToolchainSpec::Ci { .. }, ToolchainSpec::Ci { .. }) =
+                (&baseline.spec, &regressed.spec)
+            {
+                eprintln!(
+                    "remaining commits: https://github.com/rust-lang/rust/compare/{baseline:#}...{regressed:#}"
+                );
+            }
+            eprintln!("{}", "*".repeat(80).dimmed().bold());
+            eprintln!();
+            return;
+        }
+
         if toolchains[*found] == *toolchains.last().unwrap() {
             // FIXME: Ideally the BisectionResult would contain the final result.
             // This ends up testing a toolchain that was already tested.
@@ -565,6 +2702,10 @@ impl Config {
             eprintln!("checking last toolchain to determine final result");
             let t = &toolchains[*found];
             let r = match t.install(&self.client, dl_spec) {
+                Ok(()) if !t.prepare_test(self) => {
+                    remove_toolchain(self, t, dl_spec);
+                    Satisfies::Unknown
+                }
                 Ok(()) => {
                     let outcome = t.test(self);
                     remove_toolchain(self, t, dl_spec);
@@ -572,6 +2713,7 @@ impl Config {
                     match outcome {
                         TestOutcome::Baseline => Satisfies::No,
                         TestOutcome::Regressed => Satisfies::Yes,
+                        TestOutcome::Unknown => Satisfies::Unknown,
                     }
                 }
                 Err(_) => {
@@ -583,14 +2725,15 @@ impl Config {
                 Satisfies::Yes => {}
                 Satisfies::No | Satisfies::Unknown => {
                     eprintln!(
-                        "error: The regression was not found. Expanding the bounds may help."
+                        "error: The {} was not found. Expanding the bounds may help.",
+                        self.args.change_noun()
                     );
                     return;
                 }
             }
         }
 
-        let tc_found = format!("Regression in {}", toolchains[*found]);
+        let tc_found = format!("{} in {}", self.args.change_heading(), toolchains[*found]);
         eprintln!();
         eprintln!();
         eprintln!("{}", "*".repeat(80).dimmed().bold());
@@ -600,7 +2743,28 @@ impl Config {
     }
 }
 
+/// The toolchains installed by `--prefetch-boundaries`. Testing a toolchain
+/// removes it, so those still installed when this is dropped were never
+/// tested, and are removed as well unless `--preserve` is given.
+struct Prefetched<'a> {
+    cfg: &'a Config,
+    dl_spec: &'a DownloadParams,
+    toolchains: Vec<Toolchain>,
+}
+
+impl Drop for Prefetched<'_> {
+    fn drop(&mut self) {
+        for t in &self.toolchains {
+            if t.is_installed(self.dl_spec) {
+                remove_toolchain(self.cfg, t, self.dl_spec);
+            }
+        }
+    }
+}
+
 fn remove_toolchain(cfg: &Config, toolchain: &Toolchain, dl_params: &DownloadParams) {
+    toolchain.remove_toolchain_file(cfg);
+    cfg.remove_isolated_test_dir(toolchain);
     if cfg.args.preserve {
         // If `rustup toolchain link` was used to link to nightly, then even
         // with --preserve, the toolchain link should be removed, otherwise it
@@ -656,63 +2820,95 @@ fn print_final_report(
         ..
     } = ci_bisection_result;
 
-    eprintln!("{}", REPORT_HEADER.dimmed());
-    eprintln!();
-
     let (start, end) = searched_range(cfg, nightly_toolchains);
-
-    eprintln!("searched nightlies: from {} to {}", start, end);
-
-    eprintln!("regressed nightly: {}", nightly_toolchains[*nightly_found],);
-
-    eprintln!(
-        "searched commit range: https://github.com/rust-lang/rust/compare/{0}...{1}",
-        ci_toolchains.first().unwrap(),
-        ci_toolchains.last().unwrap(),
-    );
-
-    eprintln!(
-        "regressed commit: https://github.com/rust-lang/rust/commit/{}",
-        ci_toolchains[*ci_found],
-    );
-
-    eprintln!();
-    eprintln!("<details>");
-    eprintln!(
-        "<summary>bisected with <a href='{}'>cargo-bisect-rustc</a> v{}</summary>",
-        env!("CARGO_PKG_REPOSITORY"),
-        env!("CARGO_PKG_VERSION"),
-    );
-    eprintln!();
-    eprintln!();
-    if let Some(host) = option_env!("HOST") {
-        eprintln!("Host triple: {}", host);
-    }
-
-    eprintln!("Reproduce with:");
-    eprintln!("```bash");
-    eprint!("cargo bisect-rustc ");
-    for arg in env::args_os()
-        .map(|arg| arg.to_string_lossy().into_owned())
-        .skip_while(|arg| arg.ends_with("bisect-rustc"))
-    {
-        eprint!("{arg} ");
-    }
-    eprintln!();
-    eprintln!("```");
-    eprintln!("</details>");
+    let regressed_commit = format!("{:#}", ci_toolchains[*ci_found]);
+    let regressed_minor = match nightly_toolchains[*nightly_found].spec {
+        ToolchainSpec::Nightly { date } => bounds::nightly_minor_version(date),
+        _ => unreachable!("the nightly bisection only searches nightlies"),
+    };
+    let reproduce_cmd = std::iter::once("cargo bisect-rustc".to_string())
+        .chain(
+            env::args_os()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .skip_while(|arg| arg.ends_with("bisect-rustc")),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+    let finds_fix = cfg.args.finds_fix();
+    let values = [
+        (
+            "change",
+            if finds_fix { "fixed" } else { "regressed" }.to_string(),
+        ),
+        ("searched_range", format!("from {start} to {end}")),
+        (
+            "regressed_nightly",
+            nightly_toolchains[*nightly_found].to_string(),
+        ),
+        (
+            "compare_url",
+            format!(
+                "https://github.com/rust-lang/rust/compare/{0:#}...{1:#}",
+                ci_toolchains.first().unwrap(),
+                ci_toolchains.last().unwrap(),
+            ),
+        ),
+        (
+            "regressed_commit_url",
+            format!("https://github.com/rust-lang/rust/commit/{regressed_commit}"),
+        ),
+        ("regressed_commit", regressed_commit),
+        (
+            "regressed_version",
+            format!("1.{regressed_minor}.0-nightly"),
+        ),
+        (
+            "regression_label",
+            report::regression_label(regressed_minor, bounds::nightly_minor_version(today()))
+                .to_string(),
+        ),
+        ("host", cfg.args.host.clone()),
+        ("reproduce_cmd", reproduce_cmd),
+        ("version", env!("CARGO_BISECT_VERSION").to_string()),
+        ("repository", env!("CARGO_PKG_REPOSITORY").to_string()),
+    ];
+
+    let template = match &cfg.report_template {
+        Some(template) => template,
+        None => {
+            // The report of a fix belongs in the issue that was fixed rather
+            // than in a new one.
+            if !finds_fix {
+                eprintln!("{}", REPORT_HEADER.dimmed());
+                eprintln!();
+            }
+            match cfg.args.report {
+                ReportFormat::Summary => report::DEFAULT_TEMPLATE,
+                ReportFormat::GithubIssue => report::GITHUB_ISSUE_TEMPLATE,
+            }
+        }
+    };
+    // The template was checked by `Config::from_args`.
+    eprint!("{}", report::fill(template, &values).unwrap());
 }
 
 struct NightlyFinderIter {
     start_date: GitDate,
     current_date: GitDate,
+    /// The jump in days during the first week, `--finder-initial-step`.
+    initial_step: i64,
+    /// The distance in days after which the jumps are two weeks long,
+    /// `--finder-coarse-after`.
+    coarse_after: i64,
 }
 
 impl NightlyFinderIter {
-    fn new(start_date: GitDate) -> Self {
+    fn new(start_date: GitDate, initial_step: u32, coarse_after: u32) -> Self {
         Self {
             start_date,
             current_date: start_date,
+            initial_step: i64::from(initial_step),
+            coarse_after: i64::from(coarse_after),
         }
     }
 }
@@ -724,13 +2920,13 @@ impl Iterator for NightlyFinderIter {
         let current_distance = self.start_date - self.current_date;
 
         let jump_length = if current_distance.num_days() < 7 {
-            // first week jump by two days
-            2
-        } else if current_distance.num_days() < 49 {
-            // from 2nd to 7th week jump weekly
+            // first week jump by the initial step (two days by default)
+            self.initial_step
+        } else if current_distance.num_days() < self.coarse_after {
+            // then jump weekly (up to the 7th week by default)
             7
         } else {
-            // from 7th week jump by two weeks
+            // then jump by two weeks
             14
         };
 
@@ -740,82 +2936,715 @@ impl Iterator for NightlyFinderIter {
 }
 
 impl Config {
+    /// The default `--term-old` and `--term-new`, describing the outcome of a
+    /// test in the `--regress` mode. `--reverse` swaps them, since the
+    /// toolchains with the condition are then the old ones.
+    fn default_terms(&self) -> (&'static str, &'static str) {
+        let regress = self.args.regress;
+        let term_old = if self.args.script.is_some() {
+            match regress {
+                RegressOn::Error => "Script returned success",
+                RegressOn::Success => "Script returned error",
+                RegressOn::Ice => "Script did not ICE",
+                RegressOn::NonIce => "Script found ICE",
+                RegressOn::NonError => "Script returned error (no ICE)",
+                RegressOn::Slower => "Script finished within the time limit",
+            }
+        } else {
+            match regress {
+                RegressOn::Error => "Successfully compiled",
+                RegressOn::Success => "Compile error",
+                RegressOn::Ice => "Did not ICE",
+                RegressOn::NonIce => "Found ICE",
+                RegressOn::NonError => "Compile error (no ICE)",
+                RegressOn::Slower => "Finished within the time limit",
+            }
+        };
+        let term_new = if self.args.script.is_some() {
+            match regress {
+                RegressOn::Error => "Script returned error",
+                RegressOn::Success => "Script returned success",
+                RegressOn::Ice => "Script found ICE",
+                RegressOn::NonIce => "Script did not ICE",
+                RegressOn::NonError => "Script returned success or ICE",
+                RegressOn::Slower => "Script exceeded the time limit",
+            }
+        } else {
+            match regress {
+                RegressOn::Error => "Compile error",
+                RegressOn::Success => "Successfully compiled",
+                RegressOn::Ice => "Found ICE",
+                RegressOn::NonIce => "Did not ICE",
+                RegressOn::NonError => "Successfully compiled or ICE",
+                RegressOn::Slower => "Exceeded the time limit",
+            }
+        };
+        if self.args.reverse {
+            (term_new, term_old)
+        } else {
+            (term_old, term_new)
+        }
+    }
+
     fn install_and_test(
         &self,
         t: &Toolchain,
         dl_spec: &DownloadParams,
     ) -> Result<Satisfies, InstallError> {
-        let regress = self.args.regress;
-        let term_old = self.args.term_old.as_deref().unwrap_or_else(|| {
-            if self.args.script.is_some() {
-                match regress {
-                    RegressOn::Error => "Script returned success",
-                    RegressOn::Success => "Script returned error",
-                    RegressOn::Ice => "Script did not ICE",
-                    RegressOn::NonIce => "Script found ICE",
-                    RegressOn::NonError => "Script returned error (no ICE)",
+        let (default_old, default_new) = self.default_terms();
+        let term_old = self.args.term_old.as_deref().unwrap_or(default_old);
+        let term_new = self.args.term_new.as_deref().unwrap_or(default_new);
+        match t
+            .install(&self.client, dl_spec)
+            .and_then(|()| self.checkout_source(t))
+        {
+            Ok(()) if !t.prepare_test(self) => {
+                let r = Satisfies::Unknown;
+                eprintln!(
+                    "RESULT: {}, ===> {}",
+                    t,
+                    r.msg_with_context(term_old, term_new)
+                );
+                remove_toolchain(self, t, dl_spec);
+                eprintln!();
+                Ok(r)
+            }
+            Ok(()) => {
+                let outcome = self.repeat_test(t);
+                // we want to fail, so a successful build doesn't satisfy us
+                let r = match outcome {
+                    TestOutcome::Baseline => Satisfies::No,
+                    TestOutcome::Regressed => Satisfies::Yes,
+                    TestOutcome::Unknown => Satisfies::Unknown,
+                };
+                eprintln!(
+                    "RESULT: {}, ===> {}",
+                    t,
+                    r.msg_with_context(term_old, term_new)
+                );
+                remove_toolchain(self, t, dl_spec);
+                eprintln!();
+                Ok(r)
+            }
+            Err(error) => {
+                remove_toolchain(self, t, dl_spec);
+                Err(error)
+            }
+        }
+    }
+
+    /// Installs and tests a boundary of the range. Failing to install it is an
+    /// error explaining which boundary could not be tested, since bisecting
+    /// without knowing the outcome at both ends cannot be trusted.
+    fn test_boundary(
+        &self,
+        which: &str,
+        t: &Toolchain,
+        dl_spec: &DownloadParams,
+    ) -> anyhow::Result<Satisfies> {
+        self.install_and_test(t, dl_spec).map_err(|err| {
+            let hint = match err {
+                InstallError::NotFound { .. } => format!(
+                    "The artifacts may not exist for {}, or may have expired: CI builds are \
+                     only kept for {CI_RETENTION_DAYS} days. Try a different --{which}, or run \
+                     with --check-bounds to see which artifacts are available.",
+                    self.args.host
+                ),
+                InstallError::Download(DownloadError::NoNetwork(_)) => {
+                    "It is not installed yet; run without --no-network to download it.".to_string()
+                }
+                InstallError::Download(_) => "Check your network connection, or use \
+                                              --artifact-mirror to download from elsewhere."
+                    .to_string(),
+                _ => format!("Try a different --{which}."),
+            };
+            anyhow::anyhow!("could not test the {which} of the range ({t}): {err}\n{hint}")
+        })
+    }
+
+    /// Tests the start of a range of CI builds. With `--local-rustc`, the
+    /// local build stands in for the start commit. If that commit has no
+    /// artifacts left, the oldest commit that does is tested too, since the
+    /// bisection presumes it is a baseline.
+    fn test_start_boundary(
+        &self,
+        first: &Toolchain,
+        dl_spec: &DownloadParams,
+    ) -> anyhow::Result<Satisfies> {
+        let Some(path) = &self.args.local_rustc else {
+            return self.test_boundary("start", first, dl_spec);
+        };
+        let local = Toolchain {
+            spec: ToolchainSpec::Local {
+                path: fs::canonicalize(path).unwrap_or_else(|_| path.clone()),
+            },
+            host: self.args.host.clone(),
+            std_targets: self.std_targets(),
+        };
+        if self.test_boundary("start", &local, dl_spec)? == Satisfies::Yes {
+            bail!("the start of the range ({local}) includes the regression");
+        }
+        let is_start = match (&self.bounds, &first.spec) {
+            (Bounds::Commits { start, .. }, ToolchainSpec::Ci { commit, .. }) => {
+                commit.starts_with(start.as_str())
+            }
+            _ => false,
+        };
+        if is_start {
+            return Ok(Satisfies::No);
+        }
+        eprintln!("checking {first}, the oldest commit of the range with artifacts");
+        let result = self.test_boundary("start", first, dl_spec)?;
+        if result == Satisfies::Yes {
+            bail!(
+                "the regression is between {local} and {first}, the oldest commit of the range \
+                 with artifacts, so it cannot be narrowed down any further"
+            );
+        }
+        Ok(result)
+    }
+
+    /// Checks out the commit `t` was built from in the local rust repository,
+    /// if `--checkout-submodules` is given.
+    fn checkout_source(&self, t: &Toolchain) -> Result<(), InstallError> {
+        if !self.args.checkout_submodules {
+            return Ok(());
+        }
+        let sha = match t.spec {
+            ToolchainSpec::Ci { ref commit, .. } => Ok(commit.clone()),
+            ToolchainSpec::Nightly { date } => Bound::Date(date).sha(),
+            // Already built from the source it was checked out from.
+            ToolchainSpec::Local { .. } => return Ok(()),
+        };
+        sha.and_then(|sha| git::checkout_with_submodules(sha.trim()))
+            .map_err(|err| InstallError::Checkout {
+                spec: t.spec.clone(),
+                err,
+            })
+    }
+
+    /// Tests `t` as many times as `--repeat` asks for, combining the outcomes
+    /// according to `--repeat-policy`.
+    fn repeat_test(&self, t: &Toolchain) -> TestOutcome {
+        let total = self.args.repeat;
+        if total == 1 {
+            return t.test(self);
+        }
+        let (mut regressed, mut baseline) = (0, 0);
+        let outcome = loop {
+            if let Some(outcome) = self.args.repeat_policy.decide(regressed, baseline, total) {
+                break outcome;
+            }
+            eprintln!("run {} of {total}", regressed + baseline + 1);
+            match t.test(self) {
+                TestOutcome::Regressed => regressed += 1,
+                TestOutcome::Baseline => baseline += 1,
+                // Retrying won't help a comparison that could not decide.
+                TestOutcome::Unknown => break TestOutcome::Unknown,
+            }
+        };
+        if self.args.emit_cmd() {
+            eprintln!("votes for {t}: {regressed} regressed, {baseline} baseline");
+        }
+        outcome
+    }
+
+    /// Installs the given toolchains ahead of testing them, for
+    /// `--prefetch-boundaries`. Failures are only reported, since installing
+    /// is retried when the toolchain is tested. The returned guard removes
+    /// the toolchains that end up never being tested.
+    fn prefetch<'a>(
+        &'a self,
+        toolchains: &[&Toolchain],
+        dl_spec: &'a DownloadParams,
+    ) -> Prefetched<'a> {
+        let mut prefetched = Prefetched {
+            cfg: self,
+            dl_spec,
+            toolchains: Vec::new(),
+        };
+        let mut seen: Vec<&Toolchain> = Vec::new();
+        for &t in toolchains {
+            if seen.iter().any(|p| p.spec == t.spec) {
+                continue;
+            }
+            seen.push(t);
+            // Leave alone what an earlier run left installed.
+            if t.is_installed(dl_spec) {
+                continue;
+            }
+            eprintln!("prefetching {t}");
+            match t.install(&self.client, dl_spec) {
+                Ok(()) => prefetched.toolchains.push(t.clone()),
+                Err(err) => {
+                    eprintln!(
+                        "{} failed to prefetch {t}: {err}",
+                        "WARNING:".yellow().bold()
+                    );
+                    let _ = t.remove(dl_spec);
                 }
+            }
+        }
+        prefetched
+    }
+
+    /// Removes the toolchains installed in the background by
+    /// `test_while_installing` other than `t`, since only one of them is
+    /// tested next, and returns whether `t` was one of them.
+    fn take_installed_ahead(
+        &self,
+        t: &Toolchain,
+        speculated: &mut Vec<Toolchain>,
+        dl_spec: &DownloadParams,
+    ) -> bool {
+        let mut installed_ahead = false;
+        for s in speculated.drain(..) {
+            if s == *t {
+                installed_ahead = true;
             } else {
-                match regress {
-                    RegressOn::Error => "Successfully compiled",
-                    RegressOn::Success => "Compile error",
-                    RegressOn::Ice => "Did not ICE",
-                    RegressOn::NonIce => "Found ICE",
-                    RegressOn::NonError => "Compile error (no ICE)",
+                remove_toolchain(self, &s, dl_spec);
+            }
+        }
+        installed_ahead
+    }
+
+    /// Tests `t`. With `--max-parallel-installs`, installs `candidates`, the
+    /// toolchains that may be tested next, in the background meanwhile, so
+    /// that the next one is ready by the time the result is known. The
+    /// toolchains installed this way are added to `speculated`, and `t` is
+    /// `installed_ahead` if it was one of them.
+    fn test_while_installing(
+        &self,
+        t: &Toolchain,
+        installed_ahead: bool,
+        candidates: &[&Toolchain],
+        dl_spec: &DownloadParams,
+        speculated: &mut Vec<Toolchain>,
+    ) -> Satisfies {
+        let ahead_spec;
+        let t_spec = if installed_ahead {
+            ahead_spec = dl_spec.installed_ahead();
+            &ahead_spec
+        } else {
+            dl_spec
+        };
+        let max = self
+            .args
+            .max_parallel_installs
+            .map_or(0, |max| max as usize);
+        let candidates: Vec<&Toolchain> = candidates
+            .iter()
+            .copied()
+            .filter(|c| !matches!(c.spec, ToolchainSpec::Local { .. }) && !c.is_installed(dl_spec))
+            .collect();
+        if max == 0 || candidates.is_empty() {
+            return self
+                .install_and_test(t, t_spec)
+                .unwrap_or(Satisfies::Unknown);
+        }
+        std::thread::scope(|s| {
+            let installs: Vec<_> = candidates
+                .chunks(candidates.len().div_ceil(max))
+                .map(|chunk| {
+                    s.spawn(move || {
+                        toolchains::quiet_downloads();
+                        let mut installed = Vec::new();
+                        for &c in chunk {
+                            eprintln!("installing {c} in the background");
+                            match c.install(&self.client, dl_spec) {
+                                Ok(()) => installed.push(c.clone()),
+                                Err(err) => {
+                                    debug!("failed to install {c} in the background: {err}");
+                                    let _ = c.remove(dl_spec);
+                                }
+                            }
+                        }
+                        installed
+                    })
+                })
+                .collect();
+            let r = self
+                .install_and_test(t, t_spec)
+                .unwrap_or(Satisfies::Unknown);
+            for install in installs {
+                speculated.extend(install.join().unwrap_or_default());
+            }
+            r
+        })
+    }
+
+    /// Prefetches the nightlies at the start, end and middle of the range.
+    fn prefetch_nightly_bounds<'a>(&'a self, dl_spec: &'a DownloadParams) -> Prefetched<'a> {
+        let dates = match self.bounds {
+            Bounds::Dates { start, end } => {
+                let days = usize::try_from((end - start).num_days()).unwrap_or(0);
+                let probe = self.args.bias.first_probe(days + 1);
+                let offset = i64::try_from(probe).unwrap_or(0);
+                vec![start, end, start + Duration::days(offset)]
+            }
+            Bounds::SearchNightlyBackwards { end } => vec![end],
+            Bounds::Commits { .. } => unreachable!(),
+        };
+        let toolchains: Vec<_> = dates
+            .into_iter()
+            .map(|date| Toolchain {
+                spec: ToolchainSpec::Nightly { date },
+                host: self.args.host.clone(),
+                std_targets: self.std_targets(),
+            })
+            .collect();
+        self.prefetch(&toolchains.iter().collect::<Vec<_>>(), dl_spec)
+    }
+
+    /// Bisects `toolchains`, returning the index of the first regressed
+    /// toolchain. If `--max-steps` stopped the search early, the index of the
+    /// last toolchain known to pass is returned as well.
+    fn bisect_to_regression(
+        &self,
+        toolchains: &[Toolchain],
+        dl_spec: &DownloadParams,
+    ) -> anyhow::Result<(usize, Option<usize>)> {
+        let mut sampled = if self.args.detect_nonmonotonic {
+            self.check_monotonicity(toolchains, dl_spec)
+        } else {
+            HashMap::new()
+        };
+        let max_steps = self
+            .args
+            .max_steps
+            .map(|max| max.saturating_sub(self.bisect_steps.load(Ordering::Relaxed)));
+        if self.args.fail_fast_on_install_error && max_steps != Some(0) {
+            self.test_first_probe(toolchains, dl_spec, &mut sampled)?;
+        }
+        // The known ends of the range, to predict the next toolchains to test
+        // for `--max-parallel-installs`.
+        let bounds = Cell::new((0, toolchains.len() - 1));
+        let index_of = |t: &Toolchain| {
+            toolchains
+                .iter()
+                .position(|other| std::ptr::eq(other, t))
+                .unwrap()
+        };
+        let mut speculated = Vec::new();
+        // The failure of a `--step-command-fatal`, after which nothing else
+        // is tested.
+        let mut step_error = None;
+        let mut trace = self.open_trace(toolchains);
+        let result = least_satisfying(
+            toolchains,
+            self.args.bias,
+            max_steps,
+            |t, remaining, estimate| {
+                if step_error.is_some() {
+                    return Satisfies::Unknown;
+                }
+                eprintln!(
+                    "{remaining} versions remaining to test after this (roughly {estimate} steps)"
+                );
+                let installed_ahead = self.take_installed_ahead(t, &mut speculated, dl_spec);
+                let idx = index_of(t);
+                let (rm_no, lm_yes) = bounds.get();
+                let r = if let Some(&r) = sampled.get(&t.to_string()) {
+                    eprintln!("RESULT: {t}, already tested");
+                    if installed_ahead {
+                        remove_toolchain(self, t, dl_spec);
+                    }
+                    r
+                } else {
+                    self.bisect_steps.fetch_add(1, Ordering::Relaxed);
+                    let candidates: Vec<_> = next_probes(rm_no, idx, lm_yes)
+                        .into_iter()
+                        .map(|idx| &toolchains[idx])
+                        .collect();
+                    let r = self.test_while_installing(
+                        t,
+                        installed_ahead,
+                        &candidates,
+                        dl_spec,
+                        &mut speculated,
+                    );
+                    if let Err(err) = self.run_step_command(t, r) {
+                        step_error = Some(err);
+                    }
+                    r
+                };
+                if let Some(trace) = &mut trace {
+                    let _ = writeln!(
+                        trace,
+                        "probe {idx} ({t}) between no={rm_no} and yes={lm_yes}: {r:?}"
+                    );
                 }
+                r
+            },
+            |baseline, regressed, between| {
+                bounds.set((index_of(baseline), index_of(regressed)));
+                let unit = match baseline.spec {
+                    ToolchainSpec::Nightly { .. } => "nightlies",
+                    _ => "commits",
+                };
+                eprintln!(
+                    "narrowed to: baseline {baseline}, regressed {regressed} \
+                     ({between} {unit} between)"
+                );
+                self.run_on_narrow(baseline, regressed);
+            },
+        );
+        for unneeded in speculated {
+            remove_toolchain(self, &unneeded, dl_spec);
+        }
+        if let Some(err) = step_error {
+            return Err(err);
+        }
+        if let Some(trace) = &mut trace {
+            let _ = writeln!(trace, "result: {result:?}");
+        }
+        Ok(match result {
+            SearchResult::Found(found) => (found, None),
+            SearchResult::Interrupted { last_no, first_yes } => {
+                eprintln!(
+                    "stopping the bisection after {} steps as requested by --max-steps",
+                    self.bisect_steps.load(Ordering::Relaxed)
+                );
+                (first_yes, Some(last_no))
             }
-        });
-        let term_new = self.args.term_new.as_deref().unwrap_or_else(|| {
-            if self.args.script.is_some() {
-                match regress {
-                    RegressOn::Error => "Script returned error",
-                    RegressOn::Success => "Script returned success",
-                    RegressOn::Ice => "Script found ICE",
-                    RegressOn::NonIce => "Script did not ICE",
-                    RegressOn::NonError => "Script returned success or ICE",
+        })
+    }
+
+    /// Tests the toolchain the bisection starts with for
+    /// `--fail-fast-on-install-error`, adding its result to `tested`. Failing
+    /// to install it is an error, since it most likely means that none of
+    /// the toolchains can be installed.
+    fn test_first_probe(
+        &self,
+        toolchains: &[Toolchain],
+        dl_spec: &DownloadParams,
+        tested: &mut HashMap<String, Satisfies>,
+    ) -> anyhow::Result<()> {
+        if toolchains.len() < 3 {
+            return Ok(());
+        }
+        let t = &toolchains[self.args.bias.first_probe(toolchains.len())];
+        if tested.contains_key(&t.to_string()) {
+            return Ok(());
+        }
+        self.bisect_steps.fetch_add(1, Ordering::Relaxed);
+        let r = self.install_and_test(t, dl_spec).map_err(|err| {
+            anyhow::anyhow!(
+                "could not install {t}, the first toolchain to test: {err}\n\
+                 Stopping because of --fail-fast-on-install-error."
+            )
+        })?;
+        tested.insert(t.to_string(), r);
+        Ok(())
+    }
+
+    /// Appends the output of a test of `toolchain`, built for `target` if
+    /// given, to its file in `--log-dir`.
+    fn write_log(&self, toolchain: &Toolchain, target: Option<&str>, output: &process::Output) {
+        let Some(dir) = &self.args.log_dir else {
+            return;
+        };
+        let name = toolchain.rustup_name();
+        let path = dir.join(format!("{name}.log"));
+        let result = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| {
+                let target = target.unwrap_or(&self.target);
+                writeln!(file, "=== {toolchain} for {target}: {}", output.status)?;
+                writeln!(file, "--- stdout")?;
+                file.write_all(&output.stdout)?;
+                writeln!(file, "--- stderr")?;
+                file.write_all(&output.stderr)
+            });
+        match result {
+            Ok(()) => {
+                let mut logs = self.logs.lock().unwrap();
+                if !logs.iter().any(|(logged, _)| *logged == name) {
+                    logs.push((name, path));
                 }
-            } else {
-                match regress {
-                    RegressOn::Error => "Compile error",
-                    RegressOn::Success => "Successfully compiled",
-                    RegressOn::Ice => "Found ICE",
-                    RegressOn::NonIce => "Did not ICE",
-                    RegressOn::NonError => "Successfully compiled or ICE",
+            }
+            Err(err) => eprintln!(
+                "{} failed to write {}: {err}",
+                "WARNING:".yellow().bold(),
+                path.display()
+            ),
+        }
+    }
+
+    /// Removes the logs that `--keep-logs` does not keep, once the bisections
+    /// with these `results` are done.
+    fn prune_logs(&self, results: &[&BisectionResult]) {
+        let keep: Vec<String> = match self.args.keep_logs {
+            KeepLogs::All => return,
+            KeepLogs::Boundaries => results
+                .iter()
+                .flat_map(|result| {
+                    let baseline = result
+                        .last_baseline
+                        .unwrap_or(result.found.saturating_sub(1));
+                    [baseline, result.found].map(|idx| result.searched[idx].rustup_name())
+                })
+                .collect(),
+            KeepLogs::OnFailure => {
+                if results.iter().any(|result| result.last_baseline.is_some()) {
+                    return;
                 }
+                Vec::new()
             }
-        });
-        match t.install(&self.client, dl_spec) {
-            Ok(()) => {
-                let outcome = t.test(self);
-                // we want to fail, so a successful build doesn't satisfy us
-                let r = match outcome {
-                    TestOutcome::Baseline => Satisfies::No,
-                    TestOutcome::Regressed => Satisfies::Yes,
-                };
-                eprintln!(
-                    "RESULT: {}, ===> {}",
-                    t,
-                    r.msg_with_context(term_old, term_new)
-                );
-                remove_toolchain(self, t, dl_spec);
-                eprintln!();
-                Ok(r)
+        };
+        for (name, path) in self.logs.lock().unwrap().drain(..) {
+            if keep.contains(&name) {
+                continue;
             }
-            Err(error) => {
-                remove_toolchain(self, t, dl_spec);
-                Err(error)
+            if let Err(err) = fs::remove_file(&path) {
+                debug!("failed to remove {}: {err}", path.display());
             }
         }
     }
 
-    fn bisect_to_regression(&self, toolchains: &[Toolchain], dl_spec: &DownloadParams) -> usize {
-        least_satisfying(toolchains, |t, remaining, estimate| {
+    /// Opens where `--trace-bisection` writes the steps of the search: the
+    /// given file, appended to, or stderr for `-`. The search of `toolchains`
+    /// about to start is written first.
+    fn open_trace(&self, toolchains: &[Toolchain]) -> Option<Box<dyn Write>> {
+        let mut trace: Box<dyn Write> = match self.args.trace_bisection.as_ref()? {
+            path if path.as_os_str() == "-" => Box::new(io::stderr()),
+            path => match fs::OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => Box::new(file),
+                Err(err) => {
+                    eprintln!(
+                        "{} failed to open {} for --trace-bisection: {err}",
+                        "WARNING:".yellow().bold(),
+                        path.display()
+                    );
+                    return None;
+                }
+            },
+        };
+        let _ = writeln!(
+            trace,
+            "search {} toolchains from {} to {}, --bias={:?} probes {} first",
+            toolchains.len(),
+            toolchains[0],
+            toolchains[toolchains.len() - 1],
+            self.args.bias,
+            self.args.bias.first_probe(toolchains.len())
+        );
+        Some(trace)
+    }
+
+    /// Tests a few toolchains spread over the range for `--detect-nonmonotonic`,
+    /// warning if a toolchain with the regression is followed by one without
+    /// it. Returns the results by toolchain name, so the bisection can reuse
+    /// them.
+    fn check_monotonicity(
+        &self,
+        toolchains: &[Toolchain],
+        dl_spec: &DownloadParams,
+    ) -> HashMap<String, Satisfies> {
+        let samples = monotonicity_samples(toolchains.len());
+        if samples.is_empty() {
+            return HashMap::new();
+        }
+        eprintln!(
+            "checking {} toolchains spread over the range for a reverted regression",
+            samples.len()
+        );
+        // The ends of the range are known to pass and to regress.
+        let mut results = vec![(0, Satisfies::No)];
+        for idx in samples {
+            self.bisect_steps.fetch_add(1, Ordering::Relaxed);
+            let r = self
+                .install_and_test(&toolchains[idx], dl_spec)
+                .unwrap_or(Satisfies::Unknown);
+            results.push((idx, r));
+        }
+        results.push((toolchains.len() - 1, Satisfies::Yes));
+        if let Some((yes, no)) = find_nonmonotonic(&results) {
             eprintln!(
-                "{remaining} versions remaining to test after this (roughly {estimate} steps)"
+                "{} {} has the regression but the later {} does not. \
+                 The regression may have been reverted or fixed within the range, \
+                 so the result of the bisection may be misleading; \
+                 consider narrowing the range to end at {}.",
+                "WARNING:".yellow().bold(),
+                toolchains[yes],
+                toolchains[no],
+                toolchains[yes],
             );
-            self.install_and_test(t, dl_spec)
-                .unwrap_or(Satisfies::Unknown)
-        })
+        }
+        results
+            .into_iter()
+            .map(|(idx, r)| (toolchains[idx].to_string(), r))
+            .collect()
+    }
+
+    /// Runs the `--step-command`, if any, after `t` was tested with result
+    /// `r`. Failures are reported but do not stop the bisection, unless
+    /// `--step-command-fatal` is given.
+    fn run_step_command(&self, t: &Toolchain, r: Satisfies) -> anyhow::Result<()> {
+        let Some(step_command) = &self.args.step_command else {
+            return Ok(());
+        };
+        let toolchain = format!("{t:#}");
+        let result = match r {
+            Satisfies::Yes => "yes",
+            Satisfies::No => "no",
+            Satisfies::Unknown => "unknown",
+        };
+        let mut cmd = Command::new(step_command);
+        cmd.arg(&toolchain)
+            .arg(result)
+            .env("CARGO_BISECT_TOOLCHAIN", &toolchain)
+            .env("CARGO_BISECT_RESULT", result)
+            .stdin(Stdio::null());
+        if self.args.emit_cmd() {
+            eprintln!("Running `{cmd:?}`");
+        }
+        let failure = match cmd.status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => format!("--step-command exited with {status}"),
+            Err(err) => format!(
+                "failed to run --step-command `{}`: {err}",
+                step_command.display()
+            ),
+        };
+        if self.args.step_command_fatal {
+            bail!("{failure}\nStopping because of --step-command-fatal.");
+        }
+        eprintln!("{} {failure}", "WARNING:".yellow().bold());
+        Ok(())
+    }
+
+    /// Runs the `--on-narrow` command, if any, with the current candidate
+    /// range. Failures are reported but do not stop the bisection.
+    fn run_on_narrow(&self, baseline: &Toolchain, regressed: &Toolchain) {
+        let Some(on_narrow) = &self.args.on_narrow else {
+            return;
+        };
+        let baseline = format!("{baseline:#}");
+        let regressed = format!("{regressed:#}");
+        let mut cmd = Command::new(on_narrow);
+        cmd.arg(&baseline)
+            .arg(&regressed)
+            .env("CARGO_BISECT_BASELINE", &baseline)
+            .env("CARGO_BISECT_REGRESSED", &regressed)
+            .stdin(Stdio::null());
+        if self.args.emit_cmd() {
+            eprintln!("Running `{cmd:?}`");
+        }
+        match cmd.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "{} --on-narrow command exited with {status}",
+                "WARNING:".yellow().bold()
+            ),
+            Err(err) => eprintln!(
+                "{} failed to run --on-narrow command `{}`: {err}",
+                "WARNING:".yellow().bold(),
+                on_narrow.display()
+            ),
+        }
     }
 }
 
@@ -831,8 +3660,9 @@ impl Config {
         // before this date we didn't have -std packages
         let end_at = NaiveDate::from_ymd_opt(2015, 10, 20).unwrap();
         // The date where a passing build is first found. This becomes
-        // the new start point of the bisection range.
-        let mut first_success = None;
+        // the new start point of the bisection range. A --known-good-date
+        // is trusted without searching for one.
+        let mut first_success = self.args.known_good_date;
 
         // nightly_date is the date we are currently testing to find the start
         // point. The loop below modifies nightly_date towards older dates
@@ -850,7 +3680,16 @@ impl Config {
 
         let has_start = self.args.start.is_some();
 
-        let mut nightly_iter = NightlyFinderIter::new(nightly_date);
+        let prefetched = self
+            .args
+            .prefetch_boundaries
+            .then(|| self.prefetch_nightly_bounds(&dl_spec));
+
+        let mut nightly_iter = NightlyFinderIter::new(
+            nightly_date,
+            self.args.finder_initial_step,
+            self.args.finder_coarse_after,
+        );
 
         // this loop tests nightly toolchains to:
         // (1) validate that start date does not have regression (if defined on command line)
@@ -858,14 +3697,12 @@ impl Config {
         //
         // The tests here must be constrained to dates after 2015-10-20 (`end_at` date)
         // because -std packages were not available prior
-        while nightly_date > end_at {
-            let mut t = Toolchain {
+        while first_success.is_none() && nightly_date > end_at {
+            let t = Toolchain {
                 spec: ToolchainSpec::Nightly { date: nightly_date },
                 host: self.args.host.clone(),
-                std_targets: vec![self.args.host.clone(), self.target.clone()],
+                std_targets: self.std_targets(),
             };
-            t.std_targets.sort();
-            t.std_targets.dedup();
             if t.is_current_nightly() {
                 eprintln!(
                     "checking {} from the currently installed default nightly \
@@ -913,16 +3750,14 @@ impl Config {
         let first_success = first_success.context("could not find a nightly that built")?;
 
         // confirm that the end of the date range has the regression
-        let mut t_end = Toolchain {
+        let t_end = Toolchain {
             spec: ToolchainSpec::Nightly { date: last_failure },
             host: self.args.host.clone(),
-            std_targets: vec![self.args.host.clone(), self.target.clone()],
+            std_targets: self.std_targets(),
         };
-        t_end.std_targets.sort();
-        t_end.std_targets.dedup();
 
         eprintln!("checking the end range to verify it does not pass");
-        let result_nightly = self.install_and_test(&t_end, &dl_spec)?;
+        let result_nightly = self.test_boundary("end", &t_end, &dl_spec)?;
         // The regression was not identified in this nightly.
         if result_nightly == Satisfies::No {
             bail!(
@@ -932,36 +3767,41 @@ impl Config {
         }
 
         let toolchains = toolchains_between(
-            self,
+            &self.args.host,
+            &self.std_targets(),
             ToolchainSpec::Nightly {
                 date: first_success,
             },
             ToolchainSpec::Nightly { date: last_failure },
         );
 
-        let found = self.bisect_to_regression(&toolchains, &dl_spec);
+        let (found, last_baseline) = self.bisect_to_regression(&toolchains, &dl_spec)?;
+        drop(prefetched);
 
         Ok(BisectionResult {
             dl_spec,
             searched: toolchains,
             found,
+            last_baseline,
         })
     }
 }
 
-fn toolchains_between(cfg: &Config, a: ToolchainSpec, b: ToolchainSpec) -> Vec<Toolchain> {
+fn toolchains_between(
+    host: &str,
+    std_targets: &[String],
+    a: ToolchainSpec,
+    b: ToolchainSpec,
+) -> Vec<Toolchain> {
     match (a, b) {
         (ToolchainSpec::Nightly { date: a }, ToolchainSpec::Nightly { date: b }) => {
             let mut toolchains = Vec::new();
             let mut date = a;
-            let mut std_targets = vec![cfg.args.host.clone(), cfg.target.clone()];
-            std_targets.sort();
-            std_targets.dedup();
             while date <= b {
                 let t = Toolchain {
                     spec: ToolchainSpec::Nightly { date },
-                    host: cfg.args.host.clone(),
-                    std_targets: std_targets.clone(),
+                    host: host.to_string(),
+                    std_targets: std_targets.to_vec(),
                 };
                 toolchains.push(t);
                 date = date.succ_opt().unwrap();
@@ -972,17 +3812,39 @@ fn toolchains_between(cfg: &Config, a: ToolchainSpec, b: ToolchainSpec) -> Vec<T
     }
 }
 
+/// Whether the outcome of the boundaries of a CI range is already known.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BoundsStatus {
+    /// The start is known to pass and the end to regress, e.g. because they
+    /// were tested as nightlies.
+    Known,
+    /// The boundaries come from the user and need to be checked.
+    Unverified,
+}
+
 impl Config {
     // CI branch of bisect execution
     fn bisect_ci(&self, start: &str, end: &str) -> anyhow::Result<BisectionResult> {
-        eprintln!("bisecting ci builds starting at {start}, ending at {end}");
-        self.bisect_ci_via(start, end)
+        eprintln!(
+            "bisecting ci builds starting at {}, ending at {}",
+            display_sha(start),
+            display_sha(end)
+        );
+        self.bisect_ci_via(start, end, BoundsStatus::Unverified)
     }
 
-    fn bisect_ci_via(&self, start_sha: &str, end_sha: &str) -> anyhow::Result<BisectionResult> {
+    fn bisect_ci_via(
+        &self,
+        start_sha: &str,
+        end_sha: &str,
+        bounds_status: BoundsStatus,
+    ) -> anyhow::Result<BisectionResult> {
         let access = self.args.access.repo();
         let start = access.commit(start_sha)?;
         let end = access.commit(end_sha)?;
+        if bounds_status == BoundsStatus::Unverified {
+            warn_if_ci_expired(end.date, today());
+        }
         let assert_by_bors = |c: &Commit| -> anyhow::Result<()> {
             if c.committer.name != BORS_AUTHOR {
                 bail!(
@@ -997,28 +3859,63 @@ impl Config {
         };
         assert_by_bors(&start)?;
         assert_by_bors(&end)?;
-        let commits = access.commits(start_sha, &end.sha)?;
+        // From here on, the bounds are the full SHAs they resolve to, since
+        // the commits fetched are compared with them.
+        let start_sha = start.sha.as_str();
+        let mut commits = access.commits(start_sha, &end.sha)?;
 
         let Some(last) = commits.last() else {
             bail!("expected at least one commit");
         };
-        if !last.sha.starts_with(&end.sha) {
+        if last.sha != end.sha {
             bail!(
-                "expected the last commit to be {end_sha}, but got {}",
+                "expected the last commit to be {}, but got {}",
+                end.sha,
                 last.sha
             );
         }
 
-        commits.iter().zip(commits.iter().skip(1)).all(|(a, b)| {
-            let sorted_by_date = a.date <= b.date;
-            assert!(
-                sorted_by_date,
-                "commits must chronologically ordered,\
-                                 but {:?} comes after {:?}",
-                a, b
+        if let Some((a, b)) = commits
+            .iter()
+            .zip(commits.iter().skip(1))
+            .find(|(a, b)| a.date > b.date)
+        {
+            bail!(
+                "commits must be chronologically ordered, but {} ({}) comes before {} ({}).\n\
+                 The history between {start_sha} and {} may have been rewritten; \
+                 make sure both bounds are bors merge commits on the master branch.",
+                a.sha,
+                a.date,
+                b.sha,
+                b.date,
+                end.sha,
+            );
+        }
+
+        let mut end_sha = end.sha;
+        if self.excludes_end_commit() {
+            commits.pop();
+            if commits.len() < 2 {
+                bail!(
+                    "--end-exclusive leaves no commits to search between {start_sha} and {end_sha}"
+                );
+            }
+            let before = commits[commits.len() - 1].sha.clone();
+            eprintln!(
+                "--end-exclusive: searching up to {}, the commit before {}",
+                display_sha(&before),
+                display_sha(&end_sha)
             );
-            sorted_by_date
-        });
+            end_sha = before;
+        }
+
+        if !self.args.pr_author.is_empty() {
+            let skipped = retain_pr_authors(&mut commits, &self.args.pr_author);
+            eprintln!(
+                "--pr-author: skipping {skipped} commits merging PRs by other authors, {} left",
+                commits.len()
+            );
+        }
 
         for (j, commit) in commits.iter().enumerate() {
             eprintln!(
@@ -1029,7 +3926,16 @@ impl Config {
             )
         }
 
-        self.bisect_ci_in_commits(start_sha, &end.sha, commits)
+        self.bisect_ci_in_commits(start_sha, &end_sha, commits, bounds_status)
+    }
+
+    /// Whether `--end-exclusive` drops the `--end` commit from the commits to
+    /// bisect. An end date has already been moved back a day by
+    /// [`Bounds::from_args`], including when it is turned into a commit.
+    fn excludes_end_commit(&self) -> bool {
+        self.args.end_exclusive
+            && matches!(self.bounds, Bounds::Commits { .. })
+            && matches!(self.args.end, Some(Bound::Commit(_)))
     }
 
     fn bisect_ci_in_commits(
@@ -1037,20 +3943,21 @@ impl Config {
         start: &str,
         end: &str,
         mut commits: Vec<Commit>,
+        bounds_status: BoundsStatus,
     ) -> anyhow::Result<BisectionResult> {
         let dl_spec = DownloadParams::for_ci(self);
-        commits.retain(|c| today() - c.date < Duration::days(167));
+        commits.retain(|c| today() - c.date < Duration::days(CI_RETENTION_DAYS));
 
         if commits.is_empty() {
             bail!(
-                "no CI builds available between {} and {} within last 167 days",
+                "no CI builds available between {} and {} within last {CI_RETENTION_DAYS} days",
                 start,
                 end
             );
         }
 
         if let Some(c) = commits.last() {
-            if !c.sha.starts_with(end) {
+            if c.sha != end {
                 bail!("expected to end with {}, but ended with {}", end, c.sha);
             }
         }
@@ -1060,26 +3967,49 @@ impl Config {
 
         let toolchains = commits
             .into_iter()
-            .map(|commit| {
-                let mut t = Toolchain {
-                    spec: ToolchainSpec::Ci {
-                        commit: commit.sha,
-                        alt: self.args.alt,
-                    },
-                    host: self.args.host.clone(),
-                    std_targets: vec![self.args.host.clone(), self.target.clone()],
-                };
-                t.std_targets.sort();
-                t.std_targets.dedup();
-                t
-            })
+            .map(|commit| self.ci_toolchain(commit.sha))
             .collect::<Vec<_>>();
+        self.bisect_ci_toolchains(toolchains, dl_spec, bounds_status)
+    }
+
+    /// Bisects the given CI toolchains, first checking that the range
+    /// boundaries pass and regress unless they are already known to.
+    fn bisect_ci_toolchains(
+        &self,
+        toolchains: Vec<Toolchain>,
+        dl_spec: DownloadParams,
+        bounds_status: BoundsStatus,
+    ) -> anyhow::Result<BisectionResult> {
+        let prefetched = (self.args.prefetch_boundaries && !toolchains.is_empty()).then(|| {
+            // The first toolchain tested by `least_satisfying`.
+            let mid = &toolchains[self.args.bias.first_probe(toolchains.len())];
+            if bounds_status == BoundsStatus::Known {
+                self.prefetch(&[mid], &dl_spec)
+            } else {
+                self.prefetch(
+                    &[&toolchains[0], &toolchains[toolchains.len() - 1], mid],
+                    &dl_spec,
+                )
+            }
+        });
 
-        if !toolchains.is_empty() {
+        if bounds_status == BoundsStatus::Known {
+            eprintln!("skipping the checks of the range boundaries, already tested as nightlies");
+        } else if !toolchains.is_empty() {
             // validate commit at start of range
             eprintln!("checking the start range to verify it passes");
-            let start_range_result = self.install_and_test(&toolchains[0], &dl_spec)?;
+            let start_range_result = self.test_start_boundary(&toolchains[0], &dl_spec)?;
             if start_range_result == Satisfies::Yes {
+                if is_oldest_build(&self.bounds, &toolchains[0]) {
+                    bail!(
+                        "the oldest CI build available ({}) already includes the regression\n\
+                         The regression predates the CI builds that are left, since they are \
+                         only kept for {CI_RETENTION_DAYS} days, so there is no baseline to \
+                         bisect from. Bisect nightlies instead by giving --start and --end \
+                         dates, or build the older commits from source.",
+                        &toolchains[0]
+                    );
+                }
                 bail!(
                     "the commit at the start of the range ({}) includes the regression",
                     &toolchains[0]
@@ -1089,7 +4019,7 @@ impl Config {
             // validate commit at end of range
             eprintln!("checking the end range to verify it does not pass");
             let end_range_result =
-                self.install_and_test(&toolchains[toolchains.len() - 1], &dl_spec)?;
+                self.test_boundary("end", &toolchains[toolchains.len() - 1], &dl_spec)?;
             if end_range_result == Satisfies::No {
                 bail!(
                     "the commit at the end of the range ({}) does not reproduce the regression",
@@ -1098,24 +4028,19 @@ impl Config {
             }
         }
 
-        let found = self.bisect_to_regression(&toolchains, &dl_spec);
+        let (found, last_baseline) = self.bisect_to_regression(&toolchains, &dl_spec)?;
+        drop(prefetched);
 
         Ok(BisectionResult {
             searched: toolchains,
             found,
+            last_baseline,
             dl_spec,
         })
     }
 
-    fn search_perf_builds(&self, toolchain: &Toolchain) -> anyhow::Result<PerfBisectionResult> {
+    fn search_perf_builds(&self, commit: &str) -> anyhow::Result<PerfBisectionResult> {
         eprintln!("Attempting to search unrolled perf builds");
-        let Toolchain {
-            spec: ToolchainSpec::Ci { commit, .. },
-            ..
-        } = toolchain
-        else {
-            bail!("not a ci commit");
-        };
         let summary = get_commit(commit)?.summary;
         if !summary.starts_with("Auto merge of #") && !summary.contains("Rollup of") {
             bail!("not a rollup pr");
@@ -1133,8 +4058,8 @@ impl Config {
         let short_sha = context
             .builds
             .iter()
-            .map(|sha| sha.chars().take(8).collect())
-            .collect::<Vec<String>>();
+            .map(|sha| self.args.short_sha(sha))
+            .collect::<Vec<_>>();
         eprintln!("Found commits {short_sha:?}");
 
         let bisection = self.linear_in_commits(&context.builds)?;
@@ -1144,24 +4069,74 @@ impl Config {
         })
     }
 
+    /// Bisects the commits listed in `--commits-file`, in the order given,
+    /// skipping the ones without CI artifacts.
+    fn bisect_commits_file(&self, path: &Path) -> anyhow::Result<BisectionResult> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let commits = parse_commits_file(&contents);
+        eprintln!(
+            "checking CI artifacts of the {} commits in {}",
+            commits.len(),
+            path.display()
+        );
+        let mut toolchains = Vec::new();
+        for commit in commits {
+            // The artifacts are stored by full SHA, so an abbreviated one would
+            // look like a commit without artifacts.
+            let commit = if is_full_sha(&commit) {
+                commit
+            } else {
+                let sha = self
+                    .args
+                    .access
+                    .repo()
+                    .commit(&commit)
+                    .with_context(|| {
+                        format!("failed to resolve `{commit}` from {}", path.display())
+                    })?
+                    .sha;
+                eprintln!("resolved {commit} to {sha}");
+                sha
+            };
+            match ci_artifacts_exist(&self.client, &self.args, &commit) {
+                Ok(true) => toolchains.push(self.ci_toolchain(commit)),
+                Ok(false) => eprintln!("warning: skipping {commit}, it has no CI artifacts"),
+                Err(err) => eprintln!("warning: skipping {commit}, failed to check it: {err}"),
+            }
+        }
+        if toolchains.len() < 2 {
+            bail!(
+                "{} must list at least two commits with CI artifacts, found {}",
+                path.display(),
+                toolchains.len()
+            );
+        }
+        self.bisect_ci_toolchains(
+            toolchains,
+            DownloadParams::for_ci(self),
+            BoundsStatus::Unverified,
+        )
+    }
+
+    /// The toolchain of the CI build of `commit`.
+    fn ci_toolchain(&self, commit: String) -> Toolchain {
+        Toolchain {
+            spec: ToolchainSpec::Ci {
+                commit,
+                alt: self.args.alt,
+            },
+            host: self.args.host.clone(),
+            std_targets: self.std_targets(),
+        }
+    }
+
     fn linear_in_commits(&self, commits: &[&str]) -> anyhow::Result<BisectionResult> {
         let dl_spec = DownloadParams::for_ci(self);
 
         let toolchains = commits
-            .into_iter()
-            .map(|commit| {
-                let mut t = Toolchain {
-                    spec: ToolchainSpec::Ci {
-                        commit: commit.to_string(),
-                        alt: self.args.alt,
-                    },
-                    host: self.args.host.clone(),
-                    std_targets: vec![self.args.host.clone(), self.target.clone()],
-                };
-                t.std_targets.sort();
-                t.std_targets.dedup();
-                t
-            })
+            .iter()
+            .map(|commit| self.ci_toolchain((*commit).to_string()))
             .collect::<Vec<_>>();
 
         let Some(found) = toolchains.iter().position(|t| {
@@ -1175,6 +4150,7 @@ impl Config {
         Ok(BisectionResult {
             searched: toolchains,
             found,
+            last_baseline: None,
             dl_spec,
         })
     }
@@ -1184,6 +4160,10 @@ impl Config {
 struct BisectionResult {
     searched: Vec<Toolchain>,
     found: usize,
+    /// Set when `--max-steps` stopped the search early: the index of the last
+    /// toolchain known to pass. The regression is then somewhere after it, up
+    /// to and including `found`.
+    last_baseline: Option<usize>,
     dl_spec: DownloadParams,
 }
 
@@ -1287,6 +4267,86 @@ fn extract_perf_builds(body: &str) -> anyhow::Result<PerfBuildsContext<'_>> {
     })
 }
 
+/// Removes the commits merging a PR by someone other than `authors` for
+/// `--pr-author`, returning how many were removed. The ends of the range are
+/// kept, and so are rollups and commits that are not a bors merge, whose
+/// authors are unknown.
+fn retain_pr_authors(commits: &mut Vec<Commit>, authors: &[String]) -> usize {
+    let before = commits.len();
+    let last = before.saturating_sub(1);
+    let mut idx = 0;
+    commits.retain(|commit| {
+        let keep = idx == 0
+            || idx == last
+            || CulpritPr::parse(&commit.summary).is_none_or(|pr| {
+                authors
+                    .iter()
+                    .any(|author| author.eq_ignore_ascii_case(pr.author))
+            });
+        idx += 1;
+        keep
+    });
+    before - commits.len()
+}
+
+/// Whether `first`, the first toolchain of a range of CI builds, is only there
+/// because it is the oldest one with artifacts: either no `--start` was given
+/// and the range starts at [`EPOCH_COMMIT`], or the start commit has expired.
+fn is_oldest_build(bounds: &Bounds, first: &Toolchain) -> bool {
+    match (bounds, &first.spec) {
+        (Bounds::Commits { start, .. }, ToolchainSpec::Ci { commit, .. }) => {
+            start == EPOCH_COMMIT || !commit.starts_with(start.as_str())
+        }
+        _ => false,
+    }
+}
+
+/// The `--oneline` result for a CI commit, shortened to `sha`, and the number
+/// and title of its PR if known.
+fn oneline(verb: &str, sha: &str, pr: Option<(String, Option<String>)>) -> String {
+    match pr {
+        Some((number, Some(title))) => format!("{verb} in {sha} (PR #{number}): {title}"),
+        Some((number, None)) => format!("{verb} in {sha} (PR #{number})"),
+        None => format!("{verb} in {sha}"),
+    }
+}
+
+/// The PR merged by bors in a commit, for `--confirm-culprit-pr` and
+/// `--pr-author`.
+#[derive(Debug, PartialEq, Eq)]
+struct CulpritPr<'a> {
+    number: &'a str,
+    author: &'a str,
+    reviewer: &'a str,
+    title: Option<&'a str>,
+}
+
+impl<'a> CulpritPr<'a> {
+    /// Parses the message of a bors merge commit, such as
+    /// `Auto merge of #12345 - user:branch, r=reviewer` followed by the PR
+    /// title. Returns `None` for other commits and for rollups.
+    fn parse(message: &'a str) -> Option<Self> {
+        let mut lines = message.lines();
+        let header = lines.next()?.strip_prefix("Auto merge of #")?;
+        let (number, rest) = header.split_once(" - ")?;
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let (head, reviewer) = rest.split_once(", r=")?;
+        let (author, branch) = head.split_once(':')?;
+        if branch.starts_with("rollup-") || message.contains("\nRollup of ") {
+            return None;
+        }
+        let title = lines.map(str::trim).find(|line| !line.is_empty());
+        Some(CulpritPr {
+            number,
+            author,
+            reviewer: reviewer.trim(),
+            title,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1295,23 +4355,487 @@ mod tests {
     fn test_nightly_finder_iterator() {
         let start_date = NaiveDate::from_ymd_opt(2019, 01, 01).unwrap();
 
-        let iter = NightlyFinderIter::new(start_date);
+        let schedules: [(u32, u32, &[i64]); 3] = [
+            // the default schedule
+            (2, 49, &[2, 4, 6, 8, 15, 22, 29, 36, 43, 50, 64, 78]),
+            // a denser schedule for recent regressions
+            (1, 21, &[1, 2, 3, 4, 5, 6, 7, 14, 21, 35, 49]),
+            // a coarser schedule for old regressions
+            (7, 7, &[7, 21, 35, 49]),
+        ];
+        for (initial_step, coarse_after, days) in schedules {
+            let iter = NightlyFinderIter::new(start_date, initial_step, coarse_after);
+            for (date, &i) in iter.zip(days) {
+                assert_eq!(start_date - Duration::days(i), date)
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_commits_file() {
+        let contents = "\
+# suspects
+e187f8871e3d553181c9d2d4ac111197a139ca0d
+
+  25674202bb74 Auto merge of #96695
+";
+        assert_eq!(
+            parse_commits_file(contents),
+            ["e187f8871e3d553181c9d2d4ac111197a139ca0d", "25674202bb74"]
+        );
+        assert!(is_full_sha("e187f8871e3d553181c9d2d4ac111197a139ca0d"));
+        assert!(!is_full_sha("25674202bb74"));
+        assert!(!is_full_sha("origin/master"));
+    }
+
+    #[test]
+    fn test_repeat_policy() {
+        use TestOutcome::{Baseline, Regressed};
+
+        assert_eq!(RepeatPolicy::All.decide(0, 0, 3), None);
+        assert_eq!(RepeatPolicy::All.decide(2, 0, 3), None);
+        assert_eq!(RepeatPolicy::All.decide(3, 0, 3), Some(Regressed));
+        assert_eq!(RepeatPolicy::All.decide(1, 1, 3), Some(Baseline));
+
+        assert_eq!(RepeatPolicy::Majority.decide(1, 0, 3), None);
+        assert_eq!(RepeatPolicy::Majority.decide(2, 0, 3), Some(Regressed));
+        assert_eq!(RepeatPolicy::Majority.decide(1, 2, 3), Some(Baseline));
+        // A tie is not a majority.
+        assert_eq!(RepeatPolicy::Majority.decide(2, 1, 4), None);
+        assert_eq!(RepeatPolicy::Majority.decide(2, 2, 4), Some(Baseline));
+    }
+
+    #[test]
+    fn test_check_toolchains_dir() {
+        assert!(check_toolchains_dir(Path::new("/opt/rustup/toolchains")).is_ok());
+        assert!(check_toolchains_dir(Path::new("/opt/rustup")).is_err());
+        assert!(check_toolchains_dir(Path::new("toolchains")).is_err());
+    }
+
+    #[test]
+    fn test_edition_args() {
+        let args =
+            Opts::try_parse_from(["bisect", "--minimal", "--edition", "2021", "--", "main.rs"])
+                .unwrap();
+        assert_eq!(args.test_command_args(), ["main.rs", "--edition=2021"]);
+        assert!(Opts::try_parse_from(["bisect", "--edition", "2021"]).is_err());
+        assert!(Opts::try_parse_from(["bisect", "--minimal", "--edition", "2020"]).is_err());
+    }
+
+    #[test]
+    fn test_culprit_pr() {
+        let message = "Auto merge of #112908 - compiler-errors:trait-alias-unsize, r=oli-obk
+
+Don't check trait alias for object safety
+
+Fixes #112904";
+        assert_eq!(
+            CulpritPr::parse(message),
+            Some(CulpritPr {
+                number: "112908",
+                author: "compiler-errors",
+                reviewer: "oli-obk",
+                title: Some("Don't check trait alias for object safety"),
+            })
+        );
+        assert_eq!(
+            CulpritPr::parse("Auto merge of #1 - a:b, r=c")
+                .unwrap()
+                .title,
+            None
+        );
+
+        let rollup = "Auto merge of #96695 - JohnTitor:rollup-oo4fc1h, r=JohnTitor
+
+Rollup of 6 pull requests";
+        assert_eq!(CulpritPr::parse(rollup), None);
+        assert_eq!(CulpritPr::parse("Fix a typo in the docs"), None);
+    }
+
+    #[test]
+    fn test_pinned_toolchain() {
+        let toml = r#"
+[toolchain]
+channel = "1.70.0"
+components = ["rustfmt"]
+"#;
+        assert_eq!(pinned_toolchain(toml).as_deref(), Some("1.70.0"));
+        let path = "[toolchain]\npath = \"/opt/rust\"\n";
+        assert_eq!(pinned_toolchain(path).as_deref(), Some("/opt/rust"));
+        assert_eq!(
+            pinned_toolchain("nightly-2023-01-01\n").as_deref(),
+            Some("nightly-2023-01-01")
+        );
+        assert_eq!(
+            pinned_toolchain("[toolchain]\nprofile = \"minimal\"\n"),
+            None
+        );
+        assert_eq!(pinned_toolchain(""), None);
+    }
+
+    #[test]
+    fn test_oneline() {
+        let pr = |number: &str, title: Option<&str>| {
+            Some((number.to_string(), title.map(str::to_string)))
+        };
+        assert_eq!(
+            oneline(
+                "Regressed",
+                "0123abcd",
+                pr("112908", Some("Don't check trait alias for object safety"))
+            ),
+            "Regressed in 0123abcd (PR #112908): Don't check trait alias for object safety"
+        );
+        assert_eq!(
+            oneline("Fixed", "0123abcd", pr("112908", None)),
+            "Fixed in 0123abcd (PR #112908)"
+        );
+        assert_eq!(
+            oneline("Regressed", "0123abcd", None),
+            "Regressed in 0123abcd"
+        );
+    }
+
+    #[test]
+    fn test_is_oldest_build() {
+        let commits = |start: &str| Bounds::Commits {
+            start: start.to_string(),
+            end: "fedcba98".to_string(),
+        };
+        let first = ci("0123abcd4567");
+        assert!(is_oldest_build(&commits(EPOCH_COMMIT), &first));
+        // The start commit has no artifacts left.
+        assert!(is_oldest_build(&commits("89abcdef"), &first));
+        assert!(!is_oldest_build(&commits("0123abcd"), &first));
+        let dates = Bounds::Dates {
+            start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(),
+        };
+        assert!(!is_oldest_build(&dates, &first));
+    }
+
+    #[test]
+    fn test_retain_pr_authors() {
+        let commit = |sha: &str, summary: &str| Commit {
+            sha: sha.to_string(),
+            date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            summary: summary.to_string(),
+            committer: Author {
+                name: BORS_AUTHOR.to_string(),
+                email: String::new(),
+                date: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            },
+        };
+        let mut commits = vec![
+            commit("a", "Auto merge of #1 - alice:fix, r=bob"),
+            commit("b", "Auto merge of #2 - carol:feature, r=bob"),
+            commit("c", "Auto merge of #3 - Alice:other, r=bob"),
+            commit("d", "Auto merge of #4 - bob:rollup-abc1234, r=bob"),
+            commit("e", "Auto merge of #5 - dave:refactor, r=bob"),
+            commit("f", "Auto merge of #6 - erin:docs, r=bob"),
+        ];
+        assert_eq!(retain_pr_authors(&mut commits, &["alice".to_string()]), 2);
+        let shas: Vec<_> = commits.iter().map(|c| c.sha.as_str()).collect();
+        assert_eq!(shas, ["a", "c", "d", "f"]);
+    }
+
+    #[test]
+    fn test_toolchains_between_std_targets() {
+        let args = Opts::try_parse_from([
+            "bisect",
+            "--host=x86_64-unknown-linux-gnu",
+            "--target=aarch64-unknown-linux-gnu",
+            "--baseline-target=x86_64-pc-windows-gnu",
+        ])
+        .unwrap();
+        let target = args.target.clone().unwrap();
+        let date = |day| ToolchainSpec::Nightly {
+            date: NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+        };
+        let toolchains =
+            toolchains_between(&args.host, &std_targets(&args, &target), date(1), date(5));
+        assert_eq!(toolchains.len(), 5);
+        for t in &toolchains {
+            assert_eq!(
+                t.std_targets,
+                [
+                    "aarch64-unknown-linux-gnu",
+                    "x86_64-pc-windows-gnu",
+                    "x86_64-unknown-linux-gnu"
+                ]
+            );
+        }
+    }
 
-        for (date, i) in iter.zip([2, 4, 6, 8, 15, 22, 29, 36, 43, 50, 64, 78]) {
-            assert_eq!(start_date - Duration::days(i), date)
+    #[test]
+    fn test_toolchains_between_no_prebuilt_std() {
+        let args = Opts::try_parse_from([
+            "bisect",
+            "--host=x86_64-unknown-linux-gnu",
+            "--target=riscv32imc-esp-espidf",
+            "--no-prebuilt-std",
+        ])
+        .unwrap();
+        let target = args.target.clone().unwrap();
+        let date = |day| ToolchainSpec::Nightly {
+            date: NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+        };
+        let toolchains =
+            toolchains_between(&args.host, &std_targets(&args, &target), date(1), date(5));
+        for t in &toolchains {
+            assert_eq!(t.std_targets, ["x86_64-unknown-linux-gnu"]);
         }
     }
 
     #[test]
-    fn test_validate_dir() {
-        let current_dir = ".";
-        assert!(validate_dir(current_dir).is_ok());
-        let main = "src/main.rs";
-        assert!(
-            validate_dir(main).is_err(),
-            "{}",
-            validate_dir(main).unwrap_err()
+    fn test_reverse() {
+        let args = Opts::try_parse_from(["bisect", "--reverse"]).unwrap();
+        assert!(args.finds_fix());
+        assert_eq!(args.change_heading(), "Fixed");
+        let args = Opts::try_parse_from(["bisect", "--regress=success", "--reverse"]).unwrap();
+        assert!(!args.finds_fix());
+        assert_eq!(args.change_noun(), "regression");
+        assert!(Opts::try_parse_from(["bisect", "--reverse", "--prompt"]).is_err());
+    }
+
+    /// A `Config` for tests, without the network access of `Config::from_args`.
+    fn test_config(args: &[&str]) -> Config {
+        let args = Opts::try_parse_from(
+            ["bisect", "--host=x86_64-unknown-linux-gnu"]
+                .iter()
+                .chain(args),
         )
+        .unwrap();
+        Config {
+            target: args.host.clone(),
+            bounds: Bounds::Dates {
+                start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                end: NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(),
+            },
+            rustup_tmp_path: env::temp_dir(),
+            toolchains_path: env::temp_dir(),
+            client: Client::new(),
+            _test_crate_dir: None,
+            isolate_dir: None,
+            report_template: None,
+            first_runs: Mutex::default(),
+            bisect_steps: AtomicUsize::new(0),
+            logs: Mutex::new(Vec::new()),
+            args,
+        }
+    }
+
+    /// The nightly of the given day of January 2023, for tests.
+    fn nightly(day: u32) -> Toolchain {
+        Toolchain {
+            spec: ToolchainSpec::Nightly {
+                date: NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+            },
+            host: "x86_64-unknown-linux-gnu".to_string(),
+            std_targets: Vec::new(),
+        }
+    }
+
+    /// The CI build of `commit`, for tests.
+    fn ci(commit: &str) -> Toolchain {
+        Toolchain {
+            spec: ToolchainSpec::Ci {
+                commit: commit.to_string(),
+                alt: false,
+            },
+            host: "x86_64-unknown-linux-gnu".to_string(),
+            std_targets: Vec::new(),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reverse_outcome() {
+        use TestOutcome::{Baseline, Regressed};
+
+        let t = ci("0123abcd");
+        for (script, outcome) in [("true", Baseline), ("false", Regressed)] {
+            let cfg = test_config(&["--script", script]);
+            assert_eq!(t.test(&cfg), outcome, "{script}");
+            // With `--reverse`, a toolchain that still has the condition is the
+            // baseline, and one without it is where the condition was fixed.
+            let cfg = test_config(&["--script", script, "--reverse", "--explain"]);
+            assert_eq!(t.test(&cfg), outcome.reversed(), "{script} --reverse");
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_slower_outcome() {
+        use std::os::unix::fs::PermissionsExt;
+        use TestOutcome::{Baseline, Regressed};
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("slow.sh");
+        fs::write(&script, "#!/bin/sh\nsleep 0.5\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let script = script.to_str().unwrap();
+
+        let t = ci("0123abcd");
+        // Only the duration decides, so a fast failure is still the baseline.
+        for (script, max, outcome) in [
+            ("true", "5", Baseline),
+            ("false", "5", Baseline),
+            (script, "0.1", Regressed),
+            (script, "5", Baseline),
+        ] {
+            let cfg = test_config(&[
+                "--script",
+                script,
+                "--regress=slower",
+                "--max-build-seconds",
+                max,
+            ]);
+            assert_eq!(t.test(&cfg), outcome, "{script} with {max}s");
+        }
+    }
+
+    #[test]
+    fn test_check_toolchain_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let commits = Bounds::Commits {
+            start: "a".to_string(),
+            end: "b".to_string(),
+        };
+        let dates = Bounds::Dates {
+            start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2023, 1, 5).unwrap(),
+        };
+        let mut args = test_config(&[]).args;
+        args.test_dir = dir.path().to_path_buf();
+        fs::write(dir.path().join("rust-toolchain.toml"), "").unwrap();
+        // Nothing is checked without the flag.
+        assert!(check_toolchain_file(&args, &commits).is_ok());
+
+        args.toolchain_file = true;
+        assert!(check_toolchain_file(&args, &commits).is_err());
+        // An existing toolchain file would be overwritten.
+        assert!(check_toolchain_file(&args, &dates).is_err());
+        fs::remove_file(dir.path().join("rust-toolchain.toml")).unwrap();
+        assert!(check_toolchain_file(&args, &dates).is_ok());
+    }
+
+    #[test]
+    fn test_prepare_toolchain_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let t = nightly(1);
+        let mut cfg = test_config(&["--toolchain-file"]);
+        cfg.args.test_dir = dir.path().to_path_buf();
+        let file = dir.path().join("rust-toolchain.toml");
+        assert!(t.prepare_test(&cfg));
+        assert!(fs::read_to_string(&file)
+            .unwrap()
+            .contains("channel = \"nightly-2023-01-01\""));
+        t.remove_toolchain_file(&cfg);
+        assert!(!file.exists());
+
+        // A test directory that cannot be written to skips the toolchain
+        // instead of panicking.
+        cfg.args.test_dir = dir.path().join("missing");
+        assert!(!t.prepare_test(&cfg));
+    }
+
+    #[test]
+    fn test_force_install_with_parallel_installs() {
+        let dir = tempfile::tempdir().unwrap();
+        let t = nightly(1);
+        let mut cfg = test_config(&["--force-install", "--max-parallel-installs=2"]);
+        cfg.toolchains_path = dir.path().to_path_buf();
+        cfg.rustup_tmp_path = dir.path().to_path_buf();
+        let dl_spec = DownloadParams::for_nightly(&cfg);
+        // The copy installed in the background is used as is, instead of
+        // being removed and downloaded again.
+        let installed = dir.path().join(t.rustup_name()).join("installed");
+        fs::create_dir_all(&installed).unwrap();
+        t.install(&cfg.client, &dl_spec.installed_ahead()).unwrap();
+        assert!(installed.exists());
+    }
+
+    #[test]
+    fn test_prefetched_removes_untested() {
+        let dir = tempfile::tempdir().unwrap();
+        for (args, kept) in [(&[][..], false), (&["--preserve"][..], true)] {
+            let mut cfg = test_config(args);
+            cfg.toolchains_path = dir.path().to_path_buf();
+            let dl_spec = DownloadParams::for_nightly(&cfg);
+            // Testing the first one already removed it.
+            let (tested, untested) = (nightly(1), nightly(2));
+            fs::create_dir_all(dir.path().join(untested.rustup_name())).unwrap();
+            drop(Prefetched {
+                cfg: &cfg,
+                dl_spec: &dl_spec,
+                toolchains: vec![tested, untested.clone()],
+            });
+            assert_eq!(untested.is_installed(&dl_spec), kept, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn test_prune_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_dir = dir.path().to_str().unwrap();
+        let toolchains: Vec<Toolchain> = (1..=5).map(nightly).collect();
+        let output = process::Output {
+            status: process::ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        // Logs every toolchain, then returns the logs kept once a bisection
+        // found `found`, or stopped early after `last_baseline`.
+        let kept = |keep_logs: &str, found: usize, last_baseline: Option<usize>| {
+            let cfg = test_config(&["--log-dir", log_dir, "--keep-logs", keep_logs]);
+            for t in &toolchains {
+                cfg.write_log(t, None, &output);
+            }
+            let result = BisectionResult {
+                searched: toolchains.clone(),
+                found,
+                last_baseline,
+                dl_spec: DownloadParams::for_nightly(&cfg),
+            };
+            cfg.prune_logs(&[&result]);
+            let mut logs = Vec::new();
+            for entry in fs::read_dir(dir.path()).unwrap() {
+                let path = entry.unwrap().path();
+                logs.push(path.file_stem().unwrap().to_string_lossy().into_owned());
+                fs::remove_file(path).unwrap();
+            }
+            logs.sort();
+            logs
+        };
+        let names = |indices: &[usize]| -> Vec<String> {
+            indices
+                .iter()
+                .map(|&i| toolchains[i].rustup_name())
+                .collect()
+        };
+
+        assert_eq!(kept("all", 3, None), names(&[0, 1, 2, 3, 4]));
+        assert_eq!(kept("boundaries", 3, None), names(&[2, 3]));
+        // Stopped by `--max-steps` between 1 and 3.
+        assert_eq!(kept("boundaries", 3, Some(1)), names(&[1, 3]));
+        assert_eq!(kept("on-failure", 3, None), names(&[]));
+        assert_eq!(kept("on-failure", 3, Some(1)), names(&[0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_outcome_of_compare_tool_code() {
+        assert_eq!(outcome_of_compare_tool_code(Some(0)), TestOutcome::Baseline);
+        assert_eq!(
+            outcome_of_compare_tool_code(Some(1)),
+            TestOutcome::Regressed
+        );
+        assert_eq!(outcome_of_compare_tool_code(Some(2)), TestOutcome::Unknown);
+        assert_eq!(
+            outcome_of_compare_tool_code(Some(125)),
+            TestOutcome::Unknown
+        );
+        assert_eq!(outcome_of_compare_tool_code(None), TestOutcome::Unknown);
     }
 
     // Ensure the first version of the comment posted by the perf-bot works
@@ -1404,4 +4928,35 @@ In the case of a perf regression, run the following command for each PR you susp
             context.descriptions,
         );
     }
+
+    #[test]
+    fn test_resolved_config_schema() {
+        let resolved = ResolvedConfig {
+            schema_version: CONFIG_SCHEMA_VERSION,
+            host: "x86_64-unknown-linux-gnu".to_string(),
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            std_targets: vec!["x86_64-unknown-linux-gnu".to_string()],
+            components: vec!["rustc".to_string(), "cargo".to_string()],
+            toolchains_path: PathBuf::from("toolchains"),
+            tmp_path: PathBuf::from("tmp"),
+            test_dir: PathBuf::from("."),
+            access: Some("github".to_string()),
+            regress: Some("error".to_string()),
+            bounds: "search-nightly-backwards".to_string(),
+            start: None,
+            end: "2024-01-01".to_string(),
+        };
+        let serde_json::Value::Object(printed) = serde_json::to_value(&resolved).unwrap() else {
+            panic!("`ResolvedConfig` should serialize to an object");
+        };
+        assert_eq!(printed["schema_version"], CONFIG_SCHEMA_VERSION);
+
+        let schema = serde_json::to_value(schemars::schema_for!(ResolvedConfig)).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let mut keys: Vec<_> = printed.keys().collect();
+        keys.sort();
+        let mut documented: Vec<_> = properties.keys().collect();
+        documented.sort();
+        assert_eq!(keys, documented);
+    }
 }
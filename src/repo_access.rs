@@ -20,6 +20,10 @@ pub(crate) trait RustRepositoryAccessor {
     /// `end_sha` in the history, then the series will cover all commits as far
     /// back as the date associated with `start_sha`.
     fn commits(&self, start_sha: &str, end_sha: &str) -> anyhow::Result<Vec<Commit>>;
+
+    /// Looks up the `count` most recent commits ending with `end_sha`, in
+    /// chronological order.
+    fn recent_commits(&self, end_sha: &str, count: usize) -> anyhow::Result<Vec<Commit>>;
 }
 
 pub(crate) struct AccessViaLocalGit;
@@ -36,13 +40,28 @@ impl RustRepositoryAccessor for AccessViaLocalGit {
         } else {
             end_sha
         };
-        eprintln!(
+        network_log!(
             "fetching (via local git) commits from {} to {}",
-            start_sha, end_sha
+            start_sha,
+            end_sha
         );
         git::get_commits_between(start_sha, end_sha)
             .context("failed during attempt to create/access local git repository")
     }
+    fn recent_commits(&self, end_sha: &str, count: usize) -> anyhow::Result<Vec<Commit>> {
+        let end_sha = if end_sha == "origin/master" {
+            "FETCH_HEAD"
+        } else {
+            end_sha
+        };
+        network_log!(
+            "fetching (via local git) the last {} commits up to {}",
+            count,
+            end_sha
+        );
+        git::get_recent_commits(end_sha, count)
+            .context("failed during attempt to create/access local git repository")
+    }
 }
 
 impl RustRepositoryAccessor for AccessViaGithub {
@@ -62,17 +81,36 @@ impl RustRepositoryAccessor for AccessViaGithub {
             .pred_opt()
             .unwrap();
 
-        eprintln!(
+        network_log!(
             "fetching (via remote github) commits from max({}, {}) to {}",
             start_sha,
             since_date.format(crate::YYYY_MM_DD),
             end_sha
         );
 
+        let since_date = since_date.format(crate::YYYY_MM_DD).to_string();
+        let query = github::CommitsQuery {
+            since_date: Some(&since_date),
+            earliest_sha: Some(start_sha),
+            most_recent_sha: end_sha,
+            max_commits: None,
+        };
+
+        query.get_commits()
+    }
+
+    fn recent_commits(&self, end_sha: &str, count: usize) -> anyhow::Result<Vec<Commit>> {
+        network_log!(
+            "fetching (via remote github) the last {} commits up to {}",
+            count,
+            end_sha
+        );
+
         let query = github::CommitsQuery {
-            since_date: &since_date.format(crate::YYYY_MM_DD).to_string(),
-            earliest_sha: start_sha,
+            since_date: None,
+            earliest_sha: None,
             most_recent_sha: end_sha,
+            max_commits: Some(count),
         };
 
         query.get_commits()
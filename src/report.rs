@@ -0,0 +1,174 @@
+//! Filling in the final regression report from a template, for
+//! `--report-template`.
+
+/// The placeholders a report template can use.
+pub(crate) const PLACEHOLDERS: &[&str] = &[
+    "change",
+    "searched_range",
+    "regressed_nightly",
+    "compare_url",
+    "regressed_commit",
+    "regressed_commit_url",
+    "regressed_version",
+    "regression_label",
+    "host",
+    "reproduce_cmd",
+    "version",
+    "repository",
+];
+
+/// The report printed when no `--report-template` is given, meant to be
+/// pasted into an issue on the rust-lang/rust repository. `{change}` makes it
+/// read "fixed" instead of "regressed" when looking for a fix.
+pub(crate) const DEFAULT_TEMPLATE: &str = "\
+searched nightlies: {searched_range}
+{change} nightly: {regressed_nightly}
+searched commit range: {compare_url}
+{change} commit: {regressed_commit_url}
+
+<details>
+<summary>bisected with <a href='{repository}'>cargo-bisect-rustc</a> v{version}</summary>
+
+
+Host triple: {host}
+Reproduce with:
+```bash
+{reproduce_cmd}
+```
+</details>
+";
+
+/// The report for `--report=github-issue`, following the regression issue
+/// template of rust-lang/rust, with the parts only the reporter knows left as
+/// comments to fill in.
+pub(crate) const GITHUB_ISSUE_TEMPLATE: &str = "\
+### Code
+
+<!-- The code that regressed, ideally reduced to a minimal example. -->
+
+```rust
+```
+
+I expected to see this happen: <!-- explanation -->
+
+Instead, this happened: <!-- explanation -->
+
+### Version with regression
+
+The regression is in `{regressed_version}`, first in {regressed_nightly}.
+
+### Bisection
+
+searched nightlies: {searched_range}
+regressed nightly: {regressed_nightly}
+searched commit range: {compare_url}
+regressed commit: {regressed_commit_url}
+
+<details>
+<summary>bisected with <a href='{repository}'>cargo-bisect-rustc</a> v{version}</summary>
+
+
+Host triple: {host}
+Reproduce with:
+```bash
+{reproduce_cmd}
+```
+</details>
+
+@rustbot modify labels: +{regression_label}
+";
+
+/// The label of a regression that is in the `1.{regressed}.0` nightlies,
+/// depending on the channels it has reached by now, when the nightlies are
+/// `1.{nightly}.0`.
+pub(crate) fn regression_label(regressed: u32, nightly: u32) -> &'static str {
+    if regressed >= nightly {
+        "regression-from-stable-to-nightly"
+    } else if regressed + 1 == nightly {
+        "regression-from-stable-to-beta"
+    } else {
+        "regression-from-stable-to-stable"
+    }
+}
+
+/// Replaces each `{name}` in `template` with the value of `name`. `{{` and
+/// `}}` stand for literal braces.
+pub(crate) fn fill(template: &str, values: &[(&str, String)]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let brace = rest.as_bytes()[i];
+        rest = &rest[i + 1..];
+        if rest.as_bytes().first() == Some(&brace) {
+            out.push(char::from(brace));
+            rest = &rest[1..];
+            continue;
+        }
+        if brace == b'}' {
+            return Err("unmatched `}`; write `}}` for a literal brace".to_string());
+        }
+        let Some(end) = rest.find('}') else {
+            return Err("unclosed `{`; write `{{` for a literal brace".to_string());
+        };
+        let name = &rest[..end];
+        let Some((_, value)) = values.iter().find(|(key, _)| *key == name) else {
+            return Err(format!(
+                "unknown placeholder `{{{name}}}`, expected one of: {}",
+                PLACEHOLDERS.join(", ")
+            ));
+        };
+        out.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Checks that `template` only uses known placeholders, so that a mistake is
+/// reported before bisecting rather than after.
+pub(crate) fn check(template: &str) -> Result<(), String> {
+    let values: Vec<_> = PLACEHOLDERS
+        .iter()
+        .map(|name| (*name, String::new()))
+        .collect();
+    fill(template, &values).map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let values = [
+            ("host", "x86_64-unknown-linux-gnu".to_string()),
+            ("regressed_commit", "abc123".to_string()),
+        ];
+        assert_eq!(
+            fill("{regressed_commit} on {host} {{literal}}", &values).unwrap(),
+            "abc123 on x86_64-unknown-linux-gnu {literal}"
+        );
+        assert!(fill("{bogus}", &values)
+            .unwrap_err()
+            .starts_with("unknown placeholder `{bogus}`"));
+        assert!(fill("{host", &values).is_err());
+        assert!(fill("host}", &values).is_err());
+
+        assert!(check(DEFAULT_TEMPLATE).is_ok());
+        assert!(check(GITHUB_ISSUE_TEMPLATE).is_ok());
+        assert!(check("{regressed_nightly}: {compare_url}").is_ok());
+        assert!(check("{regressed}").is_err());
+    }
+
+    #[test]
+    fn test_regression_label() {
+        assert_eq!(
+            regression_label(72, 72),
+            "regression-from-stable-to-nightly"
+        );
+        assert_eq!(regression_label(71, 72), "regression-from-stable-to-beta");
+        assert_eq!(regression_label(70, 72), "regression-from-stable-to-stable");
+        assert_eq!(regression_label(60, 72), "regression-from-stable-to-stable");
+    }
+}
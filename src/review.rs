@@ -0,0 +1,139 @@
+//! Replaying a bisection from its recorded results, for `--review`.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::least_satisfying::{least_satisfying, Bias, Satisfies, SearchResult};
+
+/// A toolchain of the bisection being reviewed, with its recorded result.
+#[derive(Debug, PartialEq, Eq)]
+struct ReviewEntry {
+    name: String,
+    result: Option<Satisfies>,
+}
+
+impl fmt::Display for ReviewEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Parses a review file: the toolchains of the bisection in order, one per
+/// line, each followed by `baseline`, `regressed` or `unknown` if its result
+/// is known. Blank lines and `#` comments are ignored.
+fn parse_review(contents: &str) -> anyhow::Result<Vec<ReviewEntry>> {
+    let mut entries = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap().to_string();
+        let result = match words.next() {
+            None => None,
+            Some("baseline") => Some(Satisfies::No),
+            Some("regressed") => Some(Satisfies::Yes),
+            Some("unknown") => Some(Satisfies::Unknown),
+            Some(other) => bail!(
+                "line {}: expected `baseline`, `regressed` or `unknown` after {name}, got `{other}`",
+                i + 1
+            ),
+        };
+        entries.push(ReviewEntry { name, result });
+    }
+    Ok(entries)
+}
+
+/// Replays the bisection recorded in `path`, narrating each step as the
+/// bisection would have, without installing or testing anything.
+pub(crate) fn review(path: &Path, bias: Bias) -> anyhow::Result<()> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let entries = parse_review(&contents)?;
+    if entries.len() < 2 {
+        bail!("{} must list at least two toolchains", path.display());
+    }
+    let (first, last) = (&entries[0], &entries[entries.len() - 1]);
+    if first.result.is_some_and(|r| r != Satisfies::No) {
+        eprintln!("warning: the start of the range, {first}, is not recorded as the baseline");
+    }
+    if last.result.is_some_and(|r| r != Satisfies::Yes) {
+        eprintln!("warning: the end of the range, {last}, is not recorded as regressed");
+    }
+
+    let mut missing = None;
+    let result = least_satisfying(
+        &entries,
+        bias,
+        None,
+        |entry, remaining, estimate| {
+            eprintln!(
+                "{remaining} versions remaining to test after this (roughly {estimate} steps)"
+            );
+            let Some(result) = entry.result else {
+                eprintln!("RESULT: {entry}, ===> no recorded result");
+                missing.get_or_insert_with(|| entry.name.clone());
+                return Satisfies::Unknown;
+            };
+            let msg = result.msg_with_context("baseline", "regressed");
+            eprintln!("RESULT: {entry}, ===> {msg}");
+            result
+        },
+        |baseline, regressed, between| {
+            eprintln!(
+                "narrowed to: baseline {baseline}, regressed {regressed} \
+                 ({between} versions between)"
+            );
+        },
+    );
+    if let Some(name) = missing {
+        bail!(
+            "the bisection tests {name}, which has no recorded result; \
+             the steps after it assume it was unknown"
+        );
+    }
+    match result {
+        SearchResult::Found(found) => eprintln!("regression in {}", entries[found]),
+        SearchResult::Interrupted { last_no, first_yes } => eprintln!(
+            "regression between {} and {}",
+            entries[last_no], entries[first_yes]
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_review() {
+        let contents = "\
+# nightlies
+nightly-2023-01-01 baseline
+nightly-2023-01-02
+
+nightly-2023-01-03 unknown
+nightly-2023-01-04 regressed
+";
+        let entries = parse_review(contents).unwrap();
+        let results: Vec<_> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.result))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                ("nightly-2023-01-01", Some(Satisfies::No)),
+                ("nightly-2023-01-02", None),
+                ("nightly-2023-01-03", Some(Satisfies::Unknown)),
+                ("nightly-2023-01-04", Some(Satisfies::Yes)),
+            ]
+        );
+        assert!(parse_review("nightly-2023-01-01 passed").is_err());
+    }
+}
@@ -1,33 +1,131 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fmt;
 use std::fs;
-use std::io::{self, Read, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
 
+use anyhow::Context;
 use chrono::NaiveDate;
 use colored::Colorize;
 use dialoguer::Select;
 use flate2::read::GzDecoder;
 use log::debug;
 use pbr::{ProgressBar, Units};
-use reqwest::blocking::{Client, Response};
-use reqwest::header::CONTENT_LENGTH;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH};
 use rustc_version::Channel;
+use serde::Deserialize;
 use tar::Archive;
 use tee::TeeReader;
 use xz2::read::XzDecoder;
 
-use crate::{Config, GitDate};
+use crate::{Config, GitDate, Opts, RegressOn};
 
 pub const YYYY_MM_DD: &str = "%Y-%m-%d";
 
 pub(crate) const NIGHTLY_SERVER: &str = "https://static.rust-lang.org/dist";
 const CI_SERVER: &str = "https://ci-artifacts.rust-lang.org";
+/// Where CI artifacts are found on the [`ci_servers`], unless
+/// `--ci-url-template` says otherwise.
+pub(crate) const DEFAULT_CI_URL_TEMPLATE: &str = "{server}/rustc-builds{alt}/{commit}/{component}";
+/// The file written to the test directory for `--toolchain-file`.
+const TOOLCHAIN_FILE: &str = "rust-toolchain.toml";
+
+/// The number of characters of commit SHAs to display, set by `--sha-length`.
+/// Zero means the full SHA.
+static SHA_LENGTH: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn set_sha_length(len: usize) {
+    SHA_LENGTH.store(len, Ordering::Relaxed);
+}
+
+/// Whether `--no-network` forbids every network access.
+static NO_NETWORK: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_no_network(no_network: bool) {
+    NO_NETWORK.store(no_network, Ordering::Relaxed);
+}
+
+/// Whether `--no-network` is given, for code that falls back to what is
+/// available locally instead of failing.
+pub(crate) fn network_disabled() -> bool {
+    NO_NETWORK.load(Ordering::Relaxed)
+}
+
+/// Fails with [`DownloadError::NoNetwork`] instead of accessing `url` when
+/// `--no-network` is given, so that an offline run fails loudly rather than
+/// reaching out behind the user's back.
+pub(crate) fn check_network(url: &str) -> Result<(), DownloadError> {
+    network_allowed(network_disabled(), url)
+}
+
+fn network_allowed(no_network: bool, url: &str) -> Result<(), DownloadError> {
+    if no_network {
+        return Err(DownloadError::NoNetwork(url.to_string()));
+    }
+    Ok(())
+}
+
+/// The `--artifact-mirror` and the headers to send it, set from
+/// `--artifact-header`.
+static ARTIFACT_HEADERS: OnceLock<(String, HeaderMap)> = OnceLock::new();
+
+pub(crate) fn set_artifact_headers(args: &Opts) {
+    if let Some(mirror) = &args.artifact_mirror {
+        let headers = args.artifact_header.iter().cloned().collect();
+        let _ = ARTIFACT_HEADERS.set((mirror.trim_end_matches('/').to_string(), headers));
+    }
+}
+
+/// Adds the `--artifact-header`s to `request` if `url` is on the
+/// `--artifact-mirror`, so that they are never sent to the official servers.
+fn with_artifact_headers(request: RequestBuilder, url: &str) -> RequestBuilder {
+    match ARTIFACT_HEADERS.get() {
+        Some((mirror, headers))
+            if url
+                .strip_prefix(mirror.as_str())
+                .is_some_and(|rest| rest.starts_with('/')) =>
+        {
+            request.headers(headers.clone())
+        }
+        _ => request,
+    }
+}
+
+/// Parses a `--artifact-header` of the form `KEY:VALUE`. The value is marked
+/// sensitive so that it is not printed in debug output.
+pub(crate) fn parse_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (key, value) = crate::value_parsers::split_key_value(s, ':')?;
+    let name = HeaderName::from_bytes(key.as_bytes())
+        .map_err(|e| format!("invalid header name `{key}`: {e}"))?;
+    let mut value =
+        HeaderValue::from_str(value.trim()).map_err(|e| format!("invalid header value: {e}"))?;
+    value.set_sensitive(true);
+    Ok((name, value))
+}
+
+/// Shortens `sha` to the length requested with `--sha-length`, if any.
+pub(crate) fn display_sha(sha: &str) -> &str {
+    match SHA_LENGTH.load(Ordering::Relaxed) {
+        0 => sha,
+        len => sha.get(..len).unwrap_or(sha),
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum InstallError {
-    #[error("Could not find {spec}; url: {url}")]
-    NotFound { url: String, spec: ToolchainSpec },
+    #[error("Could not find component `{component}` for {spec}; url: {url}")]
+    NotFound {
+        url: String,
+        spec: ToolchainSpec,
+        component: String,
+    },
     #[error("Could not download toolchain: {0}")]
     Download(#[source] DownloadError),
     #[error("Could not create tempdir: {0}")]
@@ -40,12 +138,79 @@ pub(crate) enum InstallError {
         #[source]
         err: io::Error,
     },
+    #[error("Could not check out the source of {spec}: {err:#}")]
+    Checkout {
+        spec: ToolchainSpec,
+        err: anyhow::Error,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub(crate) enum TestOutcome {
     Baseline,
     Regressed,
+    /// The test could not tell, e.g. `--compare-tool` exited with another
+    /// code. The toolchain is skipped like one that failed to install.
+    Unknown,
+}
+
+impl TestOutcome {
+    /// The outcome with `--reverse`, which looks for where the condition of
+    /// a regression stops holding: a toolchain with that condition is then
+    /// the baseline.
+    pub(crate) fn reversed(self) -> Self {
+        match self {
+            TestOutcome::Baseline => TestOutcome::Regressed,
+            TestOutcome::Regressed => TestOutcome::Baseline,
+            TestOutcome::Unknown => TestOutcome::Unknown,
+        }
+    }
+}
+
+/// The observable behavior of a single test run.
+pub(crate) struct TestRun {
+    pub(crate) toolchain: String,
+    pub(crate) output: process::Output,
+    pub(crate) elapsed: Duration,
+}
+
+impl TestRun {
+    /// Returns whether both runs produced the same exit status and output,
+    /// and took roughly the same time (within 10% of each other).
+    pub(crate) fn looks_like(&self, other: &TestRun) -> bool {
+        let slowest = self.elapsed.max(other.elapsed);
+        self.output == other.output && self.elapsed.abs_diff(other.elapsed) <= slowest / 10
+    }
+}
+
+/// The first test runs of two different toolchains, to compare for
+/// [`Config::check_compiler_dependence`]. Runs repeated by `--repeat` or
+/// retried at a `--prompt` are of the same toolchain, so only the first run of
+/// each toolchain counts.
+#[derive(Default)]
+pub(crate) struct FirstRuns {
+    first: Option<TestRun>,
+    compared: bool,
+}
+
+impl FirstRuns {
+    /// Records `run`, returning it along with the first run of another
+    /// toolchain the first time there is one.
+    pub(crate) fn record(&mut self, run: TestRun) -> Option<(TestRun, TestRun)> {
+        if self.compared {
+            return None;
+        }
+        match self.first.take() {
+            Some(first) if first.toolchain != run.toolchain => {
+                self.compared = true;
+                Some((first, run))
+            }
+            first => {
+                self.first = first.or(Some(run));
+                None
+            }
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -57,11 +222,40 @@ pub(crate) struct Toolchain {
 
 impl fmt::Display for Toolchain {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.spec)
+        fmt::Display::fmt(&self.spec, f)
     }
 }
 
 impl Toolchain {
+    /// The contents of a `rust-toolchain.toml` that makes rustup install and
+    /// use this toolchain, for `--toolchain-file`. rustup can only install
+    /// nightlies, not CI builds.
+    fn toolchain_file(&self, components: &[String]) -> Option<String> {
+        let ToolchainSpec::Nightly { date } = self.spec else {
+            return None;
+        };
+        let quoted = |items: &[String]| {
+            items
+                .iter()
+                .map(|item| format!("{item:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let targets: Vec<String> = self
+            .std_targets
+            .iter()
+            .filter(|target| **target != self.host)
+            .cloned()
+            .collect();
+        Some(format!(
+            "[toolchain]\nchannel = \"nightly-{}\"\nprofile = \"minimal\"\n\
+             components = [{}]\ntargets = [{}]\n",
+            date.format(YYYY_MM_DD),
+            quoted(components),
+            quoted(&targets)
+        ))
+    }
+
     pub(crate) fn rustup_name(&self) -> String {
         match self.spec {
             ToolchainSpec::Ci { ref commit, alt } => {
@@ -77,20 +271,13 @@ impl Toolchain {
             ToolchainSpec::Nightly { ref date } => {
                 format!("bisector-nightly-{}-{}", date.format(YYYY_MM_DD), self.host)
             }
+            ToolchainSpec::Local { .. } => format!("bisector-local-{}", self.host),
         }
     }
     /// This returns the date of the default toolchain, if it is a nightly toolchain.
     /// Returns `None` if the installed toolchain is not a nightly toolchain.
     pub(crate) fn default_nightly() -> Option<GitDate> {
-        rustc_version::version_meta()
-            .ok()
-            .filter(|v| v.channel == Channel::Nightly)
-            // rustc commit date is off-by-one, see #112
-            .and_then(|v| {
-                parse_to_naive_date(&v.commit_date?)
-                    .ok()
-                    .map(|d| d.succ_opt().unwrap())
-            })
+        nightly_of_version(&rustc_version::version_meta().ok()?)
     }
 
     pub(crate) fn is_current_nightly(&self) -> bool {
@@ -103,13 +290,52 @@ impl Toolchain {
         false
     }
 
+    /// Installs the toolchain by linking to the sysroot at `path` with
+    /// `rustup toolchain link`.
+    fn link(&self, path: &str, dl_params: &DownloadParams) -> Result<(), InstallError> {
+        let mut cmd = Command::new("rustup");
+        cmd.args(["toolchain", "link", &self.rustup_name(), path]);
+        if let Some(home) = &dl_params.rustup_home {
+            cmd.env("RUSTUP_HOME", home);
+        }
+        let status = cmd.status().map_err(|err| InstallError::Subcommand {
+            cmd: format!("{cmd:?}"),
+            err,
+        })?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(InstallError::Subcommand {
+                cmd: format!("{cmd:?}"),
+                err: io::Error::new(
+                    io::ErrorKind::Other,
+                    "thiserror::Errored to link via `rustup`",
+                ),
+            })
+        }
+    }
+
+    /// Whether this toolchain is already in the toolchains directory.
+    pub(crate) fn is_installed(&self, dl_params: &DownloadParams) -> bool {
+        dl_params.install_dir.join(self.rustup_name()).is_dir()
+    }
+
     pub(crate) fn install(
         &self,
         client: &Client,
         dl_params: &DownloadParams,
     ) -> Result<(), InstallError> {
+        if dl_params.toolchain_file {
+            // rustup installs the toolchain when the test first runs it.
+            return Ok(());
+        }
         let tc_stdstream_str = format!("{self}");
         eprintln!("installing {}", tc_stdstream_str.green());
+        if let ToolchainSpec::Local { ref path } = self.spec {
+            // Replace any link left behind by an earlier run.
+            let _ = self.do_remove(dl_params);
+            return self.link(&path.to_string_lossy(), dl_params);
+        }
         let tmpdir = tempfile::Builder::new()
             .prefix(&self.rustup_name())
             .tempdir_in(&dl_params.tmp_dir)
@@ -139,30 +365,12 @@ impl Toolchain {
                         err,
                     })?
                     .stdout;
-                let output = String::from_utf8_lossy(&stdout);
-                // the output should be the path, terminated by a newline
-                let mut path = output.to_string();
-                let last = path.pop();
-                assert_eq!(last, Some('\n'));
-                path
-            };
-            let mut cmd = Command::new("rustup");
-            cmd.args(["toolchain", "link", &self.rustup_name(), &nightly_path]);
-            let status = cmd.status().map_err(|err| InstallError::Subcommand {
-                cmd: format!("{cmd:?}"),
-                err,
-            })?;
-            return if status.success() {
-                Ok(())
-            } else {
-                Err(InstallError::Subcommand {
+                parse_sysroot(&stdout).map_err(|msg| InstallError::Subcommand {
                     cmd: format!("{cmd:?}"),
-                    err: io::Error::new(
-                        io::ErrorKind::Other,
-                        "thiserror::Errored to link via `rustup`",
-                    ),
-                })
+                    err: io::Error::new(io::ErrorKind::InvalidData, msg),
+                })?
             };
+            return self.link(&nightly_path, dl_params);
         }
 
         debug!("installing via download {}", self);
@@ -170,19 +378,13 @@ impl Toolchain {
         let location = match self.spec {
             ToolchainSpec::Ci { ref commit, .. } => commit.to_string(),
             ToolchainSpec::Nightly { ref date } => date.format(YYYY_MM_DD).to_string(),
+            ToolchainSpec::Local { .. } => unreachable!("local toolchains are linked"),
         };
 
         let components = dl_params
             .components
             .iter()
-            .map(|component| {
-                if component == "rust-src" {
-                    // rust-src is target-independent
-                    "rust-src-nightly".to_string()
-                } else {
-                    format!("{component}-nightly-{}", self.host)
-                }
-            })
+            .map(|component| tarball_name(component, &self.host))
             .chain(
                 self.std_targets
                     .iter()
@@ -190,17 +392,28 @@ impl Toolchain {
             );
 
         for component in components {
-            download_tarball(
-                client,
-                &component,
-                &format!("{}/{location}/{component}.tar", dl_params.url_prefix),
-                tmpdir.path(),
-            )
-            .map_err(|e| {
+            let mut url_templates = dl_params.url_templates.iter().peekable();
+            let mut result = Ok(());
+            while let Some(url_template) = url_templates.next() {
+                result = download_tarball(
+                    client,
+                    &component,
+                    &format!("{}.tar", expand_url(url_template, &location, &component)),
+                    tmpdir.path(),
+                );
+                match result {
+                    Err(DownloadError::NotFound(ref url)) if url_templates.peek().is_some() => {
+                        eprintln!("{url} not found, trying the next server");
+                    }
+                    _ => break,
+                }
+            }
+            result.map_err(|e| {
                 if let DownloadError::NotFound(url) = e {
                     InstallError::NotFound {
                         url,
                         spec: self.spec.clone(),
+                        component: component.clone(),
                     }
                 } else {
                     InstallError::Download(e)
@@ -208,10 +421,55 @@ impl Toolchain {
             })?;
         }
 
-        fs::rename(tmpdir.into_path(), dest).map_err(InstallError::Move)
+        move_dir(&tmpdir.into_path(), &dest).map_err(InstallError::Move)
+    }
+
+    /// Checks that the installed toolchain reports the commit it was requested
+    /// for, by querying `rustc -vV`. Returns a description of the discrepancy
+    /// otherwise.
+    pub(crate) fn verify_installed(&self, dl_params: &DownloadParams) -> Result<(), String> {
+        let mut cmd = Command::new("rustc");
+        cmd.arg(format!("+{}", self.rustup_name()));
+        if let Some(home) = &dl_params.rustup_home {
+            cmd.env("RUSTUP_HOME", home);
+        }
+        let meta = rustc_version::VersionMeta::for_command(cmd)
+            .map_err(|e| format!("could not query the version of {self}: {e}"))?;
+        match self.spec {
+            ToolchainSpec::Ci { ref commit, .. } => match meta.commit_hash {
+                Some(ref hash) if hash == commit => Ok(()),
+                ref hash => Err(format!(
+                    "requested commit {commit}, but the installed rustc reports commit-hash {}",
+                    hash.as_deref().unwrap_or("<none>")
+                )),
+            },
+            ToolchainSpec::Nightly { date } => {
+                // rustc commit date is off-by-one, see #112
+                let nightly_date = meta
+                    .commit_date
+                    .as_deref()
+                    .and_then(|d| parse_to_naive_date(d).ok())
+                    .and_then(|d| d.succ_opt());
+                if nightly_date == Some(date) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "requested nightly-{}, but the installed rustc reports commit-date {} \
+                         (commit-hash {})",
+                        date.format(YYYY_MM_DD),
+                        meta.commit_date.as_deref().unwrap_or("<none>"),
+                        meta.commit_hash.as_deref().unwrap_or("<none>"),
+                    ))
+                }
+            }
+            ToolchainSpec::Local { .. } => Ok(()),
+        }
     }
 
     pub(crate) fn remove(&self, dl_params: &DownloadParams) -> io::Result<()> {
+        if dl_params.toolchain_file {
+            return Ok(());
+        }
         eprintln!("uninstalling {}", self);
         self.do_remove(dl_params)
     }
@@ -224,51 +482,179 @@ impl Toolchain {
         let rustup_name = self.rustup_name();
 
         // Guard against destroying directories that this tool didn't create.
-        assert!(
-            rustup_name.starts_with("bisector-nightly") || rustup_name.starts_with("bisector-ci")
-        );
+        assert!(is_bisector_toolchain(&rustup_name));
 
         let dir = dl_params.install_dir.join(rustup_name);
         fs::remove_dir_all(&dir)
     }
 
-    pub(crate) fn run_test(&self, cfg: &Config) -> process::Output {
+    /// The `CARGO_TARGET_DIR` of the tests, relative to the test directory.
+    /// Builds for the `--baseline-target` get their own, so that they can run
+    /// alongside the builds for `--target`.
+    fn target_dir(&self, target: Option<&str>) -> String {
+        match target {
+            Some(target) => format!("target-{}-{target}", self.rustup_name()),
+            None => format!("target-{}", self.rustup_name()),
+        }
+    }
+
+    /// Runs the test command, building for `target` instead of `--target` if
+    /// given.
+    pub(crate) fn run_test(&self, cfg: &Config, target: Option<&str>) -> process::Output {
+        let test_dir = cfg.test_dir(self);
         if !cfg.args.preserve_target {
-            let _ = fs::remove_dir_all(
-                cfg.args
-                    .test_dir
-                    .join(&format!("target-{}", self.rustup_name())),
-            );
+            let _ = fs::remove_dir_all(test_dir.join(self.target_dir(target)));
+        } else if let Some(max) = cfg.args.max_target_dirs {
+            if let Err(err) = mark_target_dir_used(&test_dir.join(self.target_dir(target))) {
+                eprintln!("warning: failed to mark the target directory as used: {err}");
+            }
+            if let Err(err) = evict_target_dirs(&test_dir, max as usize) {
+                eprintln!("warning: failed to remove old target directories: {err}");
+            }
         }
-        let script = cfg.args.script.as_ref().map(|script| {
-            if script.exists() {
-                std::env::current_dir().unwrap().join(script)
-            } else {
-                script.to_owned()
+        if cfg.args.incremental {
+            let first = self.run_command(cfg, target);
+            if !first.status.success() {
+                // Judge the first build on its own, so that a regression that
+                // does not depend on incremental compilation is not masked.
+                eprintln!("initial build failed, skipping the incremental build");
+                return first;
             }
-        });
+            eprintln!("running the incremental build...");
+        }
+        self.run_command(cfg, target)
+    }
+
+    /// Prepares the test of this toolchain: copies the test directory for
+    /// `--isolate`, writes the `rust-toolchain.toml` for `--toolchain-file`,
+    /// then runs the `--setup-script`. Returns whether all of them succeeded.
+    pub(crate) fn prepare_test(&self, cfg: &Config) -> bool {
+        self.isolate_test_dir(cfg) && self.write_toolchain_file(cfg) && self.run_setup_script(cfg)
+    }
 
-        let mut cmd = match (script, cfg.args.timeout) {
+    /// With `--isolate`, makes a fresh copy of `--test-dir` to test this
+    /// toolchain in, replacing any left from an earlier test.
+    fn isolate_test_dir(&self, cfg: &Config) -> bool {
+        if !cfg.args.isolate {
+            return true;
+        }
+        let dir = cfg.test_dir(self);
+        let _ = fs::remove_dir_all(&dir);
+        debug!(
+            "copying {} to {}",
+            cfg.args.test_dir.display(),
+            dir.display()
+        );
+        match copy_test_dir(&cfg.args.test_dir, &dir) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!(
+                    "failed to copy {} to {}: {err}",
+                    cfg.args.test_dir.display(),
+                    dir.display()
+                );
+                false
+            }
+        }
+    }
+
+    /// With `--toolchain-file`, makes the test run this toolchain by writing a
+    /// `rust-toolchain.toml` to its test directory, which `remove_toolchain`
+    /// removes again. Returns whether that succeeded.
+    fn write_toolchain_file(&self, cfg: &Config) -> bool {
+        if !cfg.args.toolchain_file {
+            return true;
+        }
+        let contents = self
+            .toolchain_file(&requested_components(&cfg.args))
+            .expect("--toolchain-file only bisects nightlies");
+        let path = cfg.test_dir(self).join(TOOLCHAIN_FILE);
+        match fs::write(&path, contents) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("failed to write {}: {err}", path.display());
+                false
+            }
+        }
+    }
+
+    /// Removes the `rust-toolchain.toml` written by `write_toolchain_file`.
+    pub(crate) fn remove_toolchain_file(&self, cfg: &Config) {
+        if cfg.args.toolchain_file {
+            let _ = fs::remove_file(cfg.test_dir(self).join(TOOLCHAIN_FILE));
+        }
+    }
+
+    /// With `--toolchain-file`, keeps `cmd` from selecting a toolchain through
+    /// `RUSTUP_TOOLCHAIN`, which would take precedence over the file.
+    fn defer_to_toolchain_file(cfg: &Config, cmd: &mut Command) {
+        if cfg.args.toolchain_file {
+            cmd.env_remove("RUSTUP_TOOLCHAIN");
+        }
+    }
+
+    /// Runs the `--setup-script`, if any, to prepare the test of this
+    /// toolchain. Returns whether it succeeded; its output is shown if not.
+    fn run_setup_script(&self, cfg: &Config) -> bool {
+        let Some(script) = cfg.args.setup_script.as_deref() else {
+            return true;
+        };
+        let invocation = script_invocation(script, cfg.args.shell.as_deref(), cfg!(windows));
+        let mut cmd = Command::new(&invocation[0]);
+        cmd.args(&invocation[1..]);
+        cmd.current_dir(cfg.test_dir(self));
+        cmd.env("RUSTUP_TOOLCHAIN", self.rustup_name());
+        cmd.env("CARGO_TARGET_DIR", self.target_dir(None));
+        if let Some(home) = cfg.custom_rustup_home() {
+            cmd.env("RUSTUP_HOME", home);
+        }
+        if cfg.args.emit_cmd() {
+            eprintln!("Running `{cmd:?}`");
+        }
+        eprintln!("running the setup script...");
+        match cmd.output() {
+            Ok(output) if output.status.success() => true,
+            Ok(output) => {
+                eprintln!("setup script failed with {}", output.status);
+                io::stderr().write_all(&output.stdout).unwrap();
+                io::stderr().write_all(&output.stderr).unwrap();
+                false
+            }
+            Err(err) => {
+                eprintln!("failed to run the setup script {}: {err}", script.display());
+                false
+            }
+        }
+    }
+
+    /// Runs the test command once.
+    fn run_command(&self, cfg: &Config, target: Option<&str>) -> process::Output {
+        let mut cmd = match (cfg.args.script.as_deref(), cfg.args.timeout) {
             (Some(script), None) => {
-                let mut cmd = Command::new(script);
+                let invocation =
+                    script_invocation(script, cfg.args.shell.as_deref(), cfg!(windows));
+                let mut cmd = Command::new(&invocation[0]);
+                cmd.args(&invocation[1..]);
                 cmd.env("RUSTUP_TOOLCHAIN", self.rustup_name());
                 cmd.args(&cfg.args.command_args);
                 cmd
             }
             (None, None) => {
-                let mut cmd = Command::new("cargo");
-                cmd.arg(&format!("+{}", self.rustup_name()));
-                if cfg.args.command_args.is_empty() {
-                    cmd.arg("build");
-                } else {
-                    cmd.args(&cfg.args.command_args);
+                let mut cmd = Command::new(cfg.args.test_tool());
+                if !cfg.args.toolchain_file {
+                    cmd.arg(&format!("+{}", self.rustup_name()));
                 }
+                cmd.args(cfg.args.test_command_args());
                 cmd
             }
             (Some(script), Some(timeout)) => {
                 let mut cmd = Command::new("timeout");
                 cmd.arg(timeout.to_string());
-                cmd.arg(script);
+                cmd.args(script_invocation(
+                    script,
+                    cfg.args.shell.as_deref(),
+                    cfg!(windows),
+                ));
                 cmd.args(&cfg.args.command_args);
                 cmd.env("RUSTUP_TOOLCHAIN", self.rustup_name());
                 cmd
@@ -276,24 +662,44 @@ impl Toolchain {
             (None, Some(timeout)) => {
                 let mut cmd = Command::new("timeout");
                 cmd.arg(timeout.to_string());
-                cmd.arg("cargo");
-                cmd.arg(format!("+{}", self.rustup_name()));
-                if cfg.args.command_args.is_empty() {
-                    cmd.arg("build");
-                } else {
-                    cmd.args(&cfg.args.command_args);
+                cmd.arg(cfg.args.test_tool());
+                if !cfg.args.toolchain_file {
+                    cmd.arg(format!("+{}", self.rustup_name()));
                 }
+                cmd.args(cfg.args.test_command_args());
                 cmd
             }
         };
-        cmd.current_dir(&cfg.args.test_dir);
-        cmd.env("CARGO_TARGET_DIR", format!("target-{}", self.rustup_name()));
-        if let Some(target) = &cfg.args.target {
+        Self::defer_to_toolchain_file(cfg, &mut cmd);
+        cmd.current_dir(cfg.test_dir(self));
+        if let Some(home) = cfg.custom_rustup_home() {
+            cmd.env("RUSTUP_HOME", home);
+        }
+        cmd.env("CARGO_TARGET_DIR", self.target_dir(target));
+        if let Some(target) = target.or(cfg.args.target.as_deref()) {
             cmd.env("CARGO_BUILD_TARGET", target);
         }
+        if cfg.args.incremental {
+            cmd.env("CARGO_INCREMENTAL", "1");
+        }
+        if !cfg.args.rustflags.is_empty() {
+            let flags = split_rustflags(&cfg.args.rustflags);
+            if cfg.args.minimal && cfg.args.script.is_none() {
+                // `rustc` is run directly and does not read RUSTFLAGS.
+                cmd.args(&flags);
+            } else {
+                let encoded = encoded_rustflags(
+                    &flags,
+                    std::env::var("CARGO_ENCODED_RUSTFLAGS").ok(),
+                    std::env::var("RUSTFLAGS").ok(),
+                );
+                cmd.env("CARGO_ENCODED_RUSTFLAGS", encoded);
+                cmd.env_remove("RUSTFLAGS");
+            }
+        }
 
         // let `cmd` capture stderr for us to process afterward.
-        let must_capture_output = cfg.args.regress.must_process_stderr();
+        let must_capture_output = cfg.args.must_capture_output();
         let emit_output = cfg.args.emit_cargo_output() || cfg.args.prompt;
 
         let default_stdio = if must_capture_output {
@@ -306,12 +712,16 @@ impl Toolchain {
 
         cmd.stdout(default_stdio());
         cmd.stderr(default_stdio());
+        if emit_output && !must_capture_output && cfg.args.oneline {
+            // Only the result goes to stdout with `--oneline`.
+            cmd.stdout(io::stderr());
+        }
 
         if cfg.args.emit_cmd() {
             eprintln!("Running `{cmd:?}`");
         }
 
-        let output = match cmd.output() {
+        let output = match bounded_output(&mut cmd, cfg.args.max_output_bytes) {
             Ok(output) => output,
             Err(err) => {
                 panic!("thiserror::Errored to run {:?}: {:?}", cmd, err);
@@ -320,17 +730,100 @@ impl Toolchain {
 
         // if we captured the stdout above but still need to emit it, then do so now
         if must_capture_output && emit_output {
-            io::stdout().write_all(&output.stdout).unwrap();
+            if cfg.args.oneline {
+                io::stderr().write_all(&output.stdout).unwrap();
+            } else {
+                io::stdout().write_all(&output.stdout).unwrap();
+            }
             io::stderr().write_all(&output.stderr).unwrap();
         }
         output
     }
 
+    /// Runs the test command, returning its output along with how long it took.
+    fn timed_run_test(&self, cfg: &Config, target: Option<&str>) -> (process::Output, Duration) {
+        cfg.remove_check_file(self);
+        let start = Instant::now();
+        let output = self.run_test(cfg, target);
+        let elapsed = start.elapsed();
+        cfg.write_log(self, target, &output);
+        if cfg.args.regress == RegressOn::Slower {
+            eprintln!("test finished in {:.2}s", elapsed.as_secs_f64());
+        }
+        if target.is_none() {
+            cfg.check_compiler_dependence(TestRun {
+                toolchain: self.to_string(),
+                output: output.clone(),
+                elapsed,
+            });
+        }
+        (output, elapsed)
+    }
+
+    /// With `--baseline-target`, a regression only counts if the test still
+    /// passes when building for the baseline target, so that changes that
+    /// break every target are not blamed for a target-specific regression.
+    fn check_baseline_target(&self, cfg: &Config, outcome: TestOutcome) -> TestOutcome {
+        let Some(baseline_target) = cfg.args.baseline_target.as_deref() else {
+            return outcome;
+        };
+        if outcome != TestOutcome::Regressed {
+            return outcome;
+        }
+        eprintln!("testing baseline target {baseline_target}...");
+        let (output, elapsed) = self.timed_run_test(cfg, Some(baseline_target));
+        self.judge_baseline_target(cfg, baseline_target, &output, elapsed)
+    }
+
+    /// The outcome of a regression on `--target`, given the output of the
+    /// test for the `--baseline-target`.
+    fn judge_baseline_target(
+        &self,
+        cfg: &Config,
+        baseline_target: &str,
+        output: &process::Output,
+        elapsed: Duration,
+    ) -> TestOutcome {
+        match cfg.default_outcome_of_output(self, output, elapsed) {
+            TestOutcome::Baseline => TestOutcome::Regressed,
+            TestOutcome::Regressed => {
+                eprintln!(
+                    "baseline target {baseline_target} fails as well, \
+                     not counting it as a target-specific regression"
+                );
+                TestOutcome::Baseline
+            }
+            TestOutcome::Unknown => TestOutcome::Unknown,
+        }
+    }
+
+    /// Like [`Toolchain::check_baseline_target`], but tests `--target` and
+    /// `--baseline-target` at the same time, for `--parallel-targets`. The
+    /// baseline target is tested even when its result turns out not to be
+    /// needed.
+    fn test_targets_in_parallel(&self, cfg: &Config, baseline_target: &str) -> TestOutcome {
+        eprintln!(
+            "testing target {} and baseline target {baseline_target}...",
+            cfg.target
+        );
+        let ((output, elapsed), baseline) = std::thread::scope(|s| {
+            let baseline = s.spawn(|| self.timed_run_test(cfg, Some(baseline_target)));
+            let target = self.timed_run_test(cfg, None);
+            (target, baseline.join().unwrap())
+        });
+        match cfg.default_outcome_of_output(self, &output, elapsed) {
+            outcome @ (TestOutcome::Baseline | TestOutcome::Unknown) => outcome,
+            TestOutcome::Regressed => {
+                self.judge_baseline_target(cfg, baseline_target, &baseline.0, baseline.1)
+            }
+        }
+    }
+
     pub(crate) fn test(&self, cfg: &Config) -> TestOutcome {
         eprintln!("testing...");
         let outcome = if cfg.args.prompt {
             loop {
-                let output = self.run_test(cfg);
+                let (output, elapsed) = self.timed_run_test(cfg, None);
                 let status = output.status;
 
                 //timeout returns exit code 124 on expiration
@@ -344,42 +837,178 @@ impl Toolchain {
                 eprintln!("\n\n{} finished with exit code {:?}.", self, status.code());
                 eprintln!("please select an action to take:");
 
-                let default_choice = match cfg.default_outcome_of_output(&output) {
+                // Retrying is never the default, so that `--assume-yes`
+                // cannot retry forever.
+                let default_choice = match cfg.default_outcome_of_output(self, &output, elapsed) {
                     TestOutcome::Regressed => 0,
                     TestOutcome::Baseline => 1,
+                    TestOutcome::Unknown => 2,
                 };
 
-                match Select::new()
-                    .items(&["mark regressed", "mark baseline", "retry"])
-                    .default(default_choice)
-                    .interact()
-                    .unwrap()
-                {
+                match select(
+                    cfg,
+                    &["mark regressed", "mark baseline", "mark unknown", "retry"],
+                    default_choice,
+                ) {
                     0 => break TestOutcome::Regressed,
                     1 => break TestOutcome::Baseline,
-                    2 => continue,
+                    2 => break TestOutcome::Unknown,
+                    3 => continue,
                     _ => unreachable!(),
                 }
             }
+        } else if let Some(baseline_target) = cfg
+            .args
+            .baseline_target
+            .as_deref()
+            .filter(|_| cfg.args.parallel_targets)
+        {
+            self.test_targets_in_parallel(cfg, baseline_target)
         } else {
-            let output = self.run_test(cfg);
-            cfg.default_outcome_of_output(&output)
+            let (output, elapsed) = self.timed_run_test(cfg, None);
+            let outcome = cfg.default_outcome_of_output(self, &output, elapsed);
+            self.check_baseline_target(cfg, outcome)
         };
 
-        outcome
+        if !cfg.args.reverse {
+            return outcome;
+        }
+        let reversed = outcome.reversed();
+        if cfg.args.explain {
+            eprintln!("explain: {self}: --reverse: {outcome:?} => {reversed:?}");
+        }
+        reversed
+    }
+}
+
+/// Asks the user to pick one of `items`, or picks `default` right away with
+/// `--assume-yes`. Every prompt should go through this, so that unattended
+/// runs never wait for input.
+fn select(cfg: &Config, items: &[&str], default: usize) -> usize {
+    if cfg.args.assume_yes {
+        eprintln!("{} (--assume-yes)", items[default]);
+        return default;
+    }
+    Select::new()
+        .items(items)
+        .default(default)
+        .interact()
+        .unwrap()
+}
+
+/// Splits the values of `--rustflags` on whitespace, as cargo does for
+/// `RUSTFLAGS`.
+fn split_rustflags(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|value| value.split_whitespace())
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the value of `CARGO_ENCODED_RUSTFLAGS` that adds `flags` to the
+/// rustflags already set in the environment. Cargo ignores `RUSTFLAGS` when
+/// `CARGO_ENCODED_RUSTFLAGS` is set, so the existing flags come from the
+/// encoded variable if present, and from `RUSTFLAGS` otherwise.
+fn encoded_rustflags(flags: &[String], encoded: Option<String>, plain: Option<String>) -> String {
+    let existing: Vec<String> = match (encoded, plain) {
+        (Some(encoded), _) if !encoded.is_empty() => {
+            encoded.split('\x1f').map(String::from).collect()
+        }
+        (None, Some(plain)) => split_rustflags(&[plain]),
+        (Some(_), _) | (None, None) => Vec::new(),
+    };
+    existing
+        .iter()
+        .chain(flags)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("\x1f")
+}
+
+/// Parses the output of `rustc --print sysroot`, which should be the path,
+/// terminated by a newline.
+fn parse_sysroot(stdout: &[u8]) -> Result<String, String> {
+    let output = String::from_utf8_lossy(stdout);
+    match output.strip_suffix('\n') {
+        Some(path) => {
+            let path = path.strip_suffix('\r').unwrap_or(path);
+            if path.is_empty() {
+                Err("unexpected empty sysroot output".to_string())
+            } else {
+                Ok(path.to_string())
+            }
+        }
+        None => Err(format!(
+            "unexpected sysroot output {output:?}, expected a path terminated by a newline"
+        )),
+    }
+}
+
+/// Makes a path to a script that exists relative to `base`, the directory
+/// `cargo-bisect-rustc` was started in, absolute. This must be done before any
+/// test runs, since those run in the test directory, where the relative path
+/// would mean something else. Anything else is looked up in `PATH`.
+///
+/// Symlinks are kept as they are rather than canonicalized, because the
+/// verbatim paths `canonicalize` returns on Windows cannot be run by `bash`.
+pub(crate) fn resolve_script(base: &Path, script: &Path) -> PathBuf {
+    let path = base.join(script);
+    if path.exists() {
+        path
+    } else {
+        script.to_owned()
     }
 }
 
+/// Returns the program and leading arguments used to run `--script`.
+///
+/// An explicit `--shell` is always used as the interpreter. Otherwise, on
+/// Windows, which cannot run scripts by their `#!` line, `.sh` scripts are run
+/// with `bash` and `.ps1` scripts with `powershell`. Everything else, and all
+/// scripts on other platforms, is executed directly.
+fn script_invocation(script: &Path, shell: Option<&str>, windows: bool) -> Vec<OsString> {
+    let mut invocation: Vec<OsString> = match shell {
+        Some(shell) => vec![shell.into()],
+        None if windows => match script.extension().and_then(|ext| ext.to_str()) {
+            Some("sh") => vec!["bash".into()],
+            Some("ps1") => vec![
+                "powershell".into(),
+                "-NoProfile".into(),
+                "-ExecutionPolicy".into(),
+                "Bypass".into(),
+                "-File".into(),
+            ],
+            _ => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    invocation.push(script.into());
+    invocation
+}
+
 pub fn parse_to_naive_date(s: &str) -> chrono::ParseResult<GitDate> {
     NaiveDate::parse_from_str(s, YYYY_MM_DD)
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub(crate) enum ToolchainSpec {
-    Ci { commit: String, alt: bool },
-    Nightly { date: GitDate },
+    Ci {
+        commit: String,
+        alt: bool,
+    },
+    Nightly {
+        date: GitDate,
+    },
+    /// A rustc built from source, given with `--local-rustc`.
+    Local {
+        path: PathBuf,
+    },
 }
 
+/// Formats CI toolchains with their SHA shortened according to
+/// `--sha-length`. The alternate form (`{:#}`) always uses the full SHA, for
+/// use in URLs.
 impl fmt::Display for ToolchainSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
@@ -389,60 +1018,288 @@ impl fmt::Display for ToolchainSpec {
                 } else {
                     String::new()
                 };
+                let commit = if f.alternate() {
+                    commit
+                } else {
+                    display_sha(commit)
+                };
                 write!(f, "{}{}", commit, alt_s)
             }
             ToolchainSpec::Nightly { ref date } => write!(f, "nightly-{}", date.format(YYYY_MM_DD)),
+            ToolchainSpec::Local { ref path } => write!(f, "local rustc {}", path.display()),
         }
     }
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct DownloadParams {
-    url_prefix: String,
+    /// The URLs to download from, tried in order when a file is missing, with
+    /// the `{commit}` and `{component}` placeholders left to fill in.
+    url_templates: Vec<String>,
     tmp_dir: PathBuf,
     install_dir: PathBuf,
+    /// The `RUSTUP_HOME` owning `install_dir`, when it isn't the default one.
+    rustup_home: Option<PathBuf>,
     components: Vec<String>,
     force_install: bool,
+    /// With `--toolchain-file`, rustup installs the toolchains itself.
+    toolchain_file: bool,
 }
 
 impl DownloadParams {
     pub(crate) fn for_ci(cfg: &Config) -> Self {
-        let url_prefix = format!(
-            "{CI_SERVER}/rustc-builds{}",
-            if cfg.args.alt { "-alt" } else { "" }
-        );
-
-        Self::from_cfg_with_url_prefix(cfg, url_prefix)
+        Self::from_cfg_with_url_templates(cfg, ci_url_templates(&cfg.args))
     }
 
     pub(crate) fn for_nightly(cfg: &Config) -> Self {
-        Self::from_cfg_with_url_prefix(cfg, NIGHTLY_SERVER.to_string())
+        // Nightlies are laid out by date the way CI builds are by commit.
+        let template = format!("{NIGHTLY_SERVER}/{{commit}}/{{component}}");
+        Self::from_cfg_with_url_templates(cfg, vec![template])
     }
 
-    fn from_cfg_with_url_prefix(cfg: &Config, url_prefix: String) -> Self {
-        let mut components = vec!["rustc".to_string()];
-        if !cfg.args.without_cargo {
-            components.push("cargo".to_string());
-        }
-        if cfg.args.with_dev {
-            components.push("rustc-dev".to_string());
-            // llvm-tools-(preview) is currently required for using rustc-dev
-            // https://github.com/rust-lang/rust/issues/72594
-            components.push("llvm-tools".to_string());
-        }
-        if cfg.args.with_src {
-            components.push("rust-src".to_string());
-        }
-        components.extend(cfg.args.components.clone());
-
+    fn from_cfg_with_url_templates(cfg: &Config, url_templates: Vec<String>) -> Self {
         DownloadParams {
-            url_prefix,
+            url_templates,
             tmp_dir: cfg.rustup_tmp_path.clone(),
             install_dir: cfg.toolchains_path.clone(),
-            components,
+            rustup_home: cfg.custom_rustup_home().map(Path::to_path_buf),
+            components: requested_components(&cfg.args),
             force_install: cfg.args.force_install,
+            toolchain_file: cfg.args.toolchain_file,
+        }
+    }
+
+    /// The parameters to install a toolchain that `--max-parallel-installs`
+    /// already installed in the background. `--force-install` replaced any
+    /// earlier copy then, so applying it again would download it twice.
+    pub(crate) fn installed_ahead(&self) -> Self {
+        DownloadParams {
+            force_install: false,
+            ..self.clone()
+        }
+    }
+
+    /// The components installed besides the standard library.
+    pub(crate) fn components(&self) -> &[String] {
+        &self.components
+    }
+}
+
+/// The date of the nightly that `rustc -Vv` describes, or `None` if it is
+/// not a nightly. Linking to the installed nightly instead of downloading it
+/// again relies on this date being right.
+fn nightly_of_version(meta: &rustc_version::VersionMeta) -> Option<GitDate> {
+    if meta.channel != Channel::Nightly {
+        return None;
+    }
+    let commit_date = parse_to_naive_date(meta.commit_date.as_deref()?).ok()?;
+    Some(nightly_date_of_commit_date(commit_date))
+}
+
+/// A nightly is built from the last commit merged before midnight UTC, and
+/// named after the day it is built on, so it is one day later than the
+/// commit date that `rustc -Vv` reports. See
+/// <https://github.com/rust-lang/cargo-bisect-rustc/issues/112>.
+fn nightly_date_of_commit_date(commit_date: GitDate) -> GitDate {
+    commit_date.succ_opt().unwrap()
+}
+
+/// Whether `rustup_name` names a toolchain installed by this tool, which it
+/// may remove.
+pub(crate) fn is_bisector_toolchain(rustup_name: &str) -> bool {
+    ["bisector-nightly", "bisector-ci", "bisector-local"]
+        .iter()
+        .any(|prefix| rustup_name.starts_with(prefix))
+}
+
+/// Runs `cmd` like `Command::output`, but keeps at most `limit` bytes of each
+/// of its stdout and stderr, so that a runaway build cannot exhaust the
+/// memory. The rest of the output is read and dropped, with a warning.
+fn bounded_output(cmd: &mut Command, limit: u64) -> io::Result<process::Output> {
+    fn read_bounded(stream: Option<impl Read>, name: &str, limit: u64) -> io::Result<Vec<u8>> {
+        let mut captured = Vec::new();
+        if let Some(mut stream) = stream {
+            (&mut stream).take(limit).read_to_end(&mut captured)?;
+            if io::copy(&mut stream, &mut io::sink())? > 0 {
+                eprintln!(
+                    "{} the test printed more than {limit} bytes to {name}, only the start \
+                     was kept (see --max-output-bytes)",
+                    "WARNING:".yellow().bold()
+                );
+            }
         }
+        Ok(captured)
     }
+
+    let mut child = cmd.spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (stdout, stderr) = std::thread::scope(|s| {
+        let stderr = s.spawn(|| read_bounded(stderr, "stderr", limit));
+        let stdout = read_bounded(stdout, "stdout", limit);
+        (stdout, stderr.join().expect("reading stderr panicked"))
+    });
+    Ok(process::Output {
+        status: child.wait()?,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+/// The file in a target directory whose modification time tells when the
+/// directory was last used, for `--max-target-dirs`. Windows cannot set the
+/// times of a directory opened like a file, so a file inside keeps them.
+const TARGET_DIR_USED: &str = ".cargo-bisect-used";
+
+/// Marks the target directory `dir` as the most recently used one for
+/// `--max-target-dirs`, creating it if needed.
+fn mark_target_dir_used(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(TARGET_DIR_USED))?
+        .set_modified(SystemTime::now())
+}
+
+/// When the target directory `dir` was last used, falling back to its own
+/// modification time if it was never marked.
+fn target_dir_last_used(dir: &Path) -> io::Result<SystemTime> {
+    match fs::metadata(dir.join(TARGET_DIR_USED)) {
+        Ok(metadata) => metadata.modified(),
+        Err(_) => fs::metadata(dir)?.modified(),
+    }
+}
+
+/// Removes the target directories of the toolchains tested in `test_dir`,
+/// except for the `keep` most recently used ones.
+fn evict_target_dirs(test_dir: &Path, keep: usize) -> io::Result<()> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(test_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_target_dir = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("target-"))
+            .is_some_and(is_bisector_toolchain);
+        if is_target_dir && entry.file_type()?.is_dir() {
+            dirs.push((target_dir_last_used(&entry.path())?, entry.path()));
+        }
+    }
+    dirs.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, dir) in dirs.into_iter().skip(keep) {
+        debug!("removing old target directory {}", dir.display());
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// The total size of the files in `path`, without following symlinks.
+pub(crate) fn dir_size(path: &Path) -> io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += dir_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Moves an extracted toolchain into place, copying it when `from` and `to`
+/// are on different filesystems and can't simply be renamed.
+fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            debug!("{} is on another filesystem, copying", to.display());
+            copy_dir(from, to)?;
+            fs::remove_dir_all(from)
+        }
+        result => result,
+    }
+}
+
+/// Copies the test directory `from` to `to` for `--isolate`, leaving out the
+/// `target` directories at its top, which only hold build output.
+fn copy_test_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let file_type = entry.file_type()?;
+        let name_str = name.to_string_lossy();
+        if file_type.is_dir() && (name_str == "target" || name_str.starts_with("target-")) {
+            continue;
+        }
+        let dest = to.join(&name);
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &dest)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &dest)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(drop)
+}
+
+/// Returns the components to install, besides the standard library, for
+/// both `--install` and the toolchains tested while bisecting.
+fn requested_components(args: &Opts) -> Vec<String> {
+    let mut components = vec!["rustc"];
+    if !args.without_cargo && !args.minimal {
+        components.push("cargo");
+    }
+    if args.with_dev {
+        components.push("rustc-dev");
+        // llvm-tools-(preview) is currently required for using rustc-dev
+        // https://github.com/rust-lang/rust/issues/72594
+        components.push("llvm-tools");
+    }
+    if args.with_src || args.no_prebuilt_std {
+        components.push("rust-src");
+    }
+    components.extend(args.components.iter().map(String::as_str));
+    if let Some(tool) = args.tool {
+        components.extend(tool.components());
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for component in components {
+        if !unique.iter().any(|c| c == component) {
+            unique.push(component.to_string());
+        }
+    }
+    unique
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -455,22 +1312,362 @@ pub(crate) enum ArchiveError {
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum DownloadError {
+    /// The URL, or URLs, that were not found.
     #[error("Tarball not found at {0}")]
     NotFound(String),
+    /// The URL that `--no-network` refused to access.
+    #[error("refusing to access {0} because of --no-network")]
+    NoNetwork(String),
     #[error("A reqwest error occurred: {0}")]
     Reqwest(#[from] reqwest::Error),
     #[error("An archive error occurred: {0}")]
     Archive(#[from] ArchiveError),
+    /// The download ended before all of its bytes arrived, so the archive
+    /// error it caused says nothing about the tarball itself.
+    #[error("Download of {url} was truncated: received {received} of {expected} bytes")]
+    Truncated {
+        url: String,
+        received: u64,
+        expected: u64,
+    },
+}
+
+/// The servers to download CI artifacts from, in order of preference: the
+/// `--artifact-mirror` if any, then the official server unless
+/// `--no-mirror-fallback` is given.
+pub(crate) fn ci_servers(args: &Opts) -> Vec<String> {
+    let mut servers = Vec::new();
+    if let Some(mirror) = &args.artifact_mirror {
+        servers.push(mirror.trim_end_matches('/').to_string());
+        if args.no_mirror_fallback {
+            return servers;
+        }
+    }
+    servers.push(CI_SERVER.to_string());
+    servers
+}
+
+/// The `--ci-url-template` for each of the [`ci_servers`], with `{server}` and
+/// `{alt}` filled in.
+fn ci_url_templates(args: &Opts) -> Vec<String> {
+    let alt_s = if args.alt { "-alt" } else { "" };
+    let mut templates: Vec<String> = ci_servers(args)
+        .iter()
+        .map(|server| {
+            args.ci_url_template
+                .replace("{server}", server)
+                .replace("{alt}", alt_s)
+        })
+        .collect();
+    // A template without `{server}` is the same for all of them.
+    templates.dedup();
+    templates
+}
+
+/// Fills in the `{commit}` and `{component}` of a URL template, giving the URL
+/// of a tarball without its `.tar.xz` or `.tar.gz` extension.
+fn expand_url(template: &str, commit: &str, component: &str) -> String {
+    template
+        .replace("{commit}", commit)
+        .replace("{component}", component)
+}
+
+/// Returns whether the CI artifacts for the given commit have been uploaded,
+/// by probing for its `rustc` tarball for `host`.
+pub(crate) fn ci_artifacts_exist(
+    client: &Client,
+    args: &Opts,
+    commit: &str,
+) -> Result<bool, DownloadError> {
+    let name = tarball_name("rustc", &args.host);
+    for template in ci_url_templates(args) {
+        if tarball_exists(client, &expand_url(&template, commit, &name))? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The components probed for by `--list-components` on CI builds, which have
+/// no manifest listing what was built.
+const CI_COMPONENTS: &[&str] = &[
+    "rustc",
+    "cargo",
+    "rust-std",
+    "rust-src",
+    "rustc-dev",
+    "llvm-tools",
+    "clippy",
+    "rustfmt",
+    "miri",
+    "rust-analyzer",
+    "rust-docs",
+];
+
+/// Returns the components of [`CI_COMPONENTS`] that the CI build of `commit`
+/// has for the host, probing each server in turn.
+pub(crate) fn ci_components(
+    client: &Client,
+    args: &Opts,
+    commit: &str,
+) -> Result<Vec<String>, DownloadError> {
+    let templates = ci_url_templates(args);
+    let mut available = Vec::new();
+    for component in CI_COMPONENTS {
+        let name = tarball_name(component, &args.host);
+        for template in &templates {
+            if tarball_exists(client, &expand_url(template, commit, &name))? {
+                available.push((*component).to_string());
+                break;
+            }
+        }
+    }
+    Ok(available)
+}
+
+/// Returns whether the nightly of the given date has a `rustc` tarball for
+/// `host`.
+pub(crate) fn nightly_artifacts_exist(
+    client: &Client,
+    date: GitDate,
+    host: &str,
+) -> Result<bool, DownloadError> {
+    let url = format!(
+        "{NIGHTLY_SERVER}/{}/{}",
+        date.format(YYYY_MM_DD),
+        tarball_name("rustc", host)
+    );
+    tarball_exists(client, &url)
+}
+
+#[derive(Deserialize)]
+struct ChannelManifest {
+    pkg: BTreeMap<String, ManifestPackage>,
+    /// The names to use for packages, such as `clippy` for `clippy-preview`.
+    #[serde(default)]
+    renames: BTreeMap<String, ManifestRename>,
+}
+
+#[derive(Deserialize)]
+struct ManifestRename {
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct ManifestPackage {
+    git_commit_hash: Option<String>,
+    target: BTreeMap<String, ManifestTarget>,
+}
+
+#[derive(Deserialize)]
+struct ManifestTarget {
+    available: bool,
+}
+
+/// Returns the components that the nightly of the given date has for `host`,
+/// according to its channel manifest.
+pub(crate) fn nightly_components(
+    client: &Client,
+    date: GitDate,
+    host: &str,
+) -> anyhow::Result<Vec<String>> {
+    let url = format!(
+        "{NIGHTLY_SERVER}/{}/channel-rust-nightly.toml",
+        date.format(YYYY_MM_DD)
+    );
+    network_log!("fetching {url}");
+    check_network(&url)?;
+    let manifest = client.get(&url).send()?.error_for_status()?.text()?;
+    manifest_components(&manifest, host)
+}
+
+/// Returns the commit that the nightly of the given date was built from,
+/// according to its channel manifest. This is a fallback for when the
+/// `channel-rust-nightly-git-commit-hash.txt` file is missing.
+pub(crate) fn nightly_manifest_commit(client: &Client, date: GitDate) -> anyhow::Result<String> {
+    let url = format!(
+        "{NIGHTLY_SERVER}/{}/channel-rust-nightly.toml",
+        date.format(YYYY_MM_DD)
+    );
+    network_log!("fetching {url}");
+    check_network(&url)?;
+    let manifest = client.get(&url).send()?.error_for_status()?.text()?;
+    manifest_commit(&manifest).with_context(|| format!("failed to read the commit from {url}"))
+}
+
+/// Parses a channel manifest, returning the commit `rustc` was built from.
+fn manifest_commit(manifest: &str) -> anyhow::Result<String> {
+    let manifest: ChannelManifest = toml::from_str(manifest)?;
+    manifest
+        .pkg
+        .get("rustc")
+        .and_then(|pkg| pkg.git_commit_hash.clone())
+        .context("the manifest has no `git_commit_hash` for rustc")
+}
+
+/// Parses a channel manifest, returning the components available for `host`,
+/// including target-independent ones like `rust-src`, by the names `-c`
+/// takes: packages like `clippy-preview` are renamed to `clippy`.
+fn manifest_components(manifest: &str, host: &str) -> anyhow::Result<Vec<String>> {
+    let manifest: ChannelManifest = toml::from_str(manifest)?;
+    let short_name = |name: String| {
+        manifest
+            .renames
+            .iter()
+            .find(|(_, rename)| rename.to == name)
+            .map(|(short, _)| short.clone())
+            .or_else(|| name.strip_suffix("-preview").map(String::from))
+            .unwrap_or(name)
+    };
+    let mut components: Vec<String> = manifest
+        .pkg
+        .iter()
+        // `rust` is the combined installer rather than a component.
+        .filter(|(name, _)| *name != "rust")
+        .filter(|(_, pkg)| {
+            [host, "*"]
+                .iter()
+                .any(|target| pkg.target.get(*target).is_some_and(|t| t.available))
+        })
+        .map(|(name, _)| short_name(name.clone()))
+        .collect();
+    components.sort();
+    components.dedup();
+    Ok(components)
+}
+
+/// The base name of a component's tarball, without the `.tar.*` extension.
+fn tarball_name(component: &str, host: &str) -> String {
+    if component == "rust-src" {
+        // rust-src is target-independent
+        "rust-src-nightly".to_string()
+    } else {
+        format!("{component}-nightly-{host}")
+    }
+}
+
+fn tarball_exists(client: &Client, url: &str) -> Result<bool, DownloadError> {
+    for ext in ["xz", "gz"] {
+        let url = format!("{url}.tar.{ext}");
+        debug!("probing <{}>...", url);
+        check_network(&url)?;
+        let response = with_artifact_headers(client.head(&url), &url).send()?;
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Reports the progress of a download. This is a progress bar when stdout is a
+/// terminal, and occasional plain lines otherwise, so that CI logs and pipes
+/// don't fill up with carriage returns.
+pub(crate) enum DownloadProgress {
+    Bar(Box<ProgressBar<io::Stdout>>),
+    /// Downloads in the background with `--max-parallel-installs` report
+    /// nothing, so as not to garble the output of the test running meanwhile.
+    Quiet,
+    Lines {
+        name: String,
+        total: u64,
+        received: u64,
+        next_report: u64,
+    },
+}
+
+/// How often `DownloadProgress::Lines` reports when the total size is unknown.
+const PLAIN_PROGRESS_INTERVAL: u64 = 16 * 1024 * 1024;
+
+thread_local! {
+    static QUIET_DOWNLOADS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Stops reporting the progress of the downloads made by the current thread.
+pub(crate) fn quiet_downloads() {
+    QUIET_DOWNLOADS.set(true);
+}
+
+impl DownloadProgress {
+    fn new(name: &str, total: u64) -> Self {
+        if QUIET_DOWNLOADS.get() {
+            DownloadProgress::Quiet
+        } else if io::stdout().is_terminal() {
+            let mut bar = ProgressBar::new(total);
+            bar.set_units(Units::Bytes);
+            bar.message(&format!("{name}: "));
+            DownloadProgress::Bar(Box::new(bar))
+        } else {
+            DownloadProgress::Lines {
+                name: name.to_string(),
+                total,
+                received: 0,
+                next_report: Self::report_interval(total),
+            }
+        }
+    }
+
+    fn report_interval(total: u64) -> u64 {
+        if total == 0 {
+            PLAIN_PROGRESS_INTERVAL
+        } else {
+            // Report roughly every quarter.
+            total.div_ceil(4)
+        }
+    }
+}
+
+impl Write for DownloadProgress {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            DownloadProgress::Bar(bar) => bar.write(buf),
+            DownloadProgress::Lines {
+                name,
+                total,
+                received,
+                next_report,
+            } => {
+                *received += buf.len() as u64;
+                if *received >= *next_report {
+                    match (*received * 100).checked_div(*total) {
+                        Some(percent) => eprintln!("{name}: {percent}%"),
+                        None => eprintln!("{name}: {} MiB", *received / (1024 * 1024)),
+                    }
+                    *next_report += Self::report_interval(*total);
+                }
+                Ok(buf.len())
+            }
+            DownloadProgress::Quiet => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            DownloadProgress::Bar(bar) => bar.flush(),
+            DownloadProgress::Lines { .. } | DownloadProgress::Quiet => Ok(()),
+        }
+    }
 }
 
 pub(crate) fn download_progress(
     client: &Client,
     name: &str,
     url: &str,
-) -> Result<TeeReader<Response, ProgressBar<io::Stdout>>, DownloadError> {
+) -> Result<TeeReader<Response, DownloadProgress>, DownloadError> {
+    let (response, length) = download_response(client, url)?;
+    Ok(TeeReader::new(
+        response,
+        DownloadProgress::new(name, length),
+    ))
+}
+
+/// Starts downloading `url`, returning the response and its Content-Length,
+/// or 0 if the server did not send one.
+fn download_response(client: &Client, url: &str) -> Result<(Response, u64), DownloadError> {
     debug!("downloading <{}>...", url);
+    check_network(url)?;
 
-    let response = client.get(url).send()?;
+    let response = with_artifact_headers(client.get(url), url).send()?;
 
     if response.status() == reqwest::StatusCode::NOT_FOUND {
         return Err(DownloadError::NotFound(url.to_string()));
@@ -482,11 +1679,54 @@ pub(crate) fn download_progress(
         .get(CONTENT_LENGTH)
         .and_then(|c| c.to_str().ok()?.parse().ok())
         .unwrap_or(0);
-    let mut bar = ProgressBar::new(length);
-    bar.set_units(Units::Bytes);
-    bar.message(&format!("{name}: "));
 
-    Ok(TeeReader::new(response, bar))
+    Ok((response, length))
+}
+
+/// A tarball download that counts the bytes it received, to tell a download
+/// that ended early from a tarball that is malformed.
+struct TarballDownload {
+    reader: TeeReader<Response, DownloadProgress>,
+    received: u64,
+    expected: u64,
+}
+
+impl TarballDownload {
+    fn start(client: &Client, name: &str, url: &str) -> Result<Self, DownloadError> {
+        let (response, expected) = download_response(client, url)?;
+        Ok(TarballDownload {
+            reader: TeeReader::new(response, DownloadProgress::new(name, expected)),
+            received: 0,
+            expected,
+        })
+    }
+
+    /// Turns the result of extracting the download into a `DownloadError`,
+    /// reporting it as truncated when fewer bytes arrived than announced.
+    fn finish(mut self, url: &str, result: Result<(), ArchiveError>) -> Result<(), DownloadError> {
+        let Err(err) = result else {
+            return Ok(());
+        };
+        // The decoder may have stopped reading early; read what is left so
+        // that only a download that really ended early counts as truncated.
+        let _ = io::copy(&mut self, &mut io::sink());
+        if self.expected != 0 && self.received < self.expected {
+            return Err(DownloadError::Truncated {
+                url: url.to_string(),
+                received: self.received,
+                expected: self.expected,
+            });
+        }
+        Err(DownloadError::Archive(err))
+    }
+}
+
+impl Read for TarballDownload {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.received += n as u64;
+        Ok(n)
+    }
 }
 
 fn download_tar_xz(
@@ -495,8 +1735,9 @@ fn download_tar_xz(
     url: &str,
     dest: &Path,
 ) -> Result<(), DownloadError> {
-    let response = XzDecoder::new(download_progress(client, name, url)?);
-    unarchive(response, dest).map_err(DownloadError::Archive)
+    let mut download = TarballDownload::start(client, name, url)?;
+    let result = unarchive(XzDecoder::new(&mut download), dest);
+    download.finish(url, result)
 }
 
 fn download_tar_gz(
@@ -505,8 +1746,24 @@ fn download_tar_gz(
     url: &str,
     dest: &Path,
 ) -> Result<(), DownloadError> {
-    let response = GzDecoder::new(download_progress(client, name, url)?);
-    unarchive(response, dest).map_err(DownloadError::Archive)
+    let mut download = TarballDownload::start(client, name, url)?;
+    let result = unarchive(GzDecoder::new(&mut download), dest);
+    download.finish(url, result)
+}
+
+/// Runs `download` again once if it was truncated, which is usually a flaky
+/// connection rather than a problem with the tarball. Files extracted by the
+/// first attempt are overwritten by the second.
+fn retry_truncated(
+    mut download: impl FnMut() -> Result<(), DownloadError>,
+) -> Result<(), DownloadError> {
+    match download() {
+        Err(err @ DownloadError::Truncated { .. }) => {
+            eprintln!("{err}, downloading it again");
+            download()
+        }
+        res => res,
+    }
 }
 
 fn unarchive<R: Read>(r: R, dest: &Path) -> Result<(), ArchiveError> {
@@ -535,16 +1792,537 @@ fn unarchive<R: Read>(r: R, dest: &Path) -> Result<(), ArchiveError> {
     Ok(())
 }
 
+/// Downloads a `.tar.xz` tarball, or the `.tar.gz` one of older builds that
+/// don't have it. When neither exists, the build itself is most likely
+/// missing, and the error names both URLs.
 fn download_tarball(
     client: &Client,
     name: &str,
     url: &str,
     dest: &Path,
 ) -> Result<(), DownloadError> {
-    match download_tar_xz(client, name, &format!("{url}.xz"), dest) {
-        Err(DownloadError::NotFound { .. }) => {
-            download_tar_gz(client, name, &format!("{url}.gz"), dest)
+    let xz_url = format!("{url}.xz");
+    match retry_truncated(|| download_tar_xz(client, name, &xz_url, dest)) {
+        Err(DownloadError::NotFound(_)) => {}
+        res => return res,
+    }
+    let gz_url = format!("{url}.gz");
+    match retry_truncated(|| download_tar_gz(client, name, &gz_url, dest)) {
+        Ok(()) => {
+            debug!("{xz_url} not found, used {gz_url} of an older build instead");
+            Ok(())
         }
-        res => res,
+        Err(DownloadError::NotFound(_)) => {
+            Err(DownloadError::NotFound(format!("{xz_url} or {gz_url}")))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_requested_components() {
+        let components = |args: &[&str]| {
+            let args = Opts::try_parse_from(["cargo-bisect-rustc"].iter().chain(args)).unwrap();
+            requested_components(&args)
+        };
+        assert_eq!(components(&[]), ["rustc", "cargo"]);
+        assert_eq!(components(&["--without-cargo"]), ["rustc"]);
+        assert_eq!(
+            components(&["--target=x86_64-unknown-none", "--no-prebuilt-std"]),
+            ["rustc", "cargo", "rust-src"]
+        );
+        assert_eq!(
+            components(&["--with-dev", "--with-src", "-c", "rust-src", "-c", "miri"]),
+            [
+                "rustc",
+                "cargo",
+                "rustc-dev",
+                "llvm-tools",
+                "rust-src",
+                "miri"
+            ]
+        );
+        assert_eq!(
+            components(&["--tool=clippy", "-c", "clippy"]),
+            ["rustc", "cargo", "clippy"]
+        );
+        // `--install` resolves the same components as a bisection.
+        assert_eq!(
+            components(&["--install", "2023-01-01", "--with-src"]),
+            components(&["--start", "2023-01-01", "--with-src"])
+        );
+    }
+
+    #[test]
+    fn test_toolchain_file() {
+        let host = "x86_64-unknown-linux-gnu".to_string();
+        let nightly = Toolchain {
+            spec: ToolchainSpec::Nightly {
+                date: NaiveDate::from_ymd_opt(2023, 3, 18).unwrap(),
+            },
+            host: host.clone(),
+            std_targets: vec![host.clone(), "wasm32-unknown-unknown".to_string()],
+        };
+        let components = ["rustc".to_string(), "cargo".to_string()];
+        assert_eq!(
+            nightly.toolchain_file(&components).unwrap(),
+            "[toolchain]\n\
+             channel = \"nightly-2023-03-18\"\n\
+             profile = \"minimal\"\n\
+             components = [\"rustc\", \"cargo\"]\n\
+             targets = [\"wasm32-unknown-unknown\"]\n"
+        );
+
+        let ci = Toolchain {
+            spec: ToolchainSpec::Ci {
+                commit: "a".repeat(40),
+                alt: false,
+            },
+            host: host.clone(),
+            std_targets: vec![host],
+        };
+        assert!(ci.toolchain_file(&components).is_none());
+    }
+
+    #[test]
+    fn test_encoded_rustflags() {
+        let flags = split_rustflags(&[
+            "-C target-cpu=native".to_string(),
+            "-Copt-level=3".to_string(),
+        ]);
+        assert_eq!(flags, ["-C", "target-cpu=native", "-Copt-level=3"]);
+        assert_eq!(
+            encoded_rustflags(&flags, None, None),
+            "-C\x1ftarget-cpu=native\x1f-Copt-level=3"
+        );
+        assert_eq!(
+            encoded_rustflags(&flags[2..], None, Some(" --cfg  foo ".to_string())),
+            "--cfg\x1ffoo\x1f-Copt-level=3"
+        );
+        assert_eq!(
+            encoded_rustflags(
+                &flags[2..],
+                Some("-L\x1f/path with spaces".to_string()),
+                Some("--cfg foo".to_string())
+            ),
+            "-L\x1f/path with spaces\x1f-Copt-level=3"
+        );
+        assert_eq!(
+            encoded_rustflags(&flags[2..], Some(String::new()), None),
+            "-Copt-level=3"
+        );
+    }
+
+    #[test]
+    fn test_parse_sysroot() {
+        assert_eq!(
+            parse_sysroot(b"/home/user/.rustup/toolchains/nightly\n").unwrap(),
+            "/home/user/.rustup/toolchains/nightly"
+        );
+        assert_eq!(
+            parse_sysroot(b"C:\\rustup\\toolchains\\nightly\r\n").unwrap(),
+            "C:\\rustup\\toolchains\\nightly"
+        );
+        assert!(parse_sysroot(b"").is_err());
+        assert!(parse_sysroot(b"\n").is_err());
+        assert!(parse_sysroot(b"/no/newline").is_err());
+    }
+
+    #[test]
+    fn test_manifest_commit() {
+        let manifest = r#"
+manifest-version = "2"
+date = "2023-03-18"
+
+[pkg.rustc]
+version = "1.70.0-nightly (9d0eac4d0 2023-03-17)"
+git_commit_hash = "9d0eac4d0234c3f1ad2a1e5b8be0d8d0a1c1d56f"
+
+[pkg.rustc.target.x86_64-unknown-linux-gnu]
+available = true
+"#;
+        assert_eq!(
+            manifest_commit(manifest).unwrap(),
+            "9d0eac4d0234c3f1ad2a1e5b8be0d8d0a1c1d56f"
+        );
+        let without_hash = r"
+[pkg.rustc.target.x86_64-unknown-linux-gnu]
+available = true
+";
+        assert!(manifest_commit(without_hash).is_err());
+    }
+
+    #[test]
+    fn test_manifest_components() {
+        let manifest = r#"
+manifest-version = "2"
+date = "2023-03-10"
+
+[pkg.rust.target.x86_64-unknown-linux-gnu]
+available = true
+
+[pkg.rustc.target.x86_64-unknown-linux-gnu]
+available = true
+
+[pkg.rustc.target.aarch64-apple-darwin]
+available = true
+
+[pkg.miri-preview.target.x86_64-unknown-linux-gnu]
+available = false
+
+[pkg.rustc-dev.target.aarch64-apple-darwin]
+available = true
+
+[pkg.rust-src.target."*"]
+available = true
+
+[pkg.clippy-preview.target.x86_64-unknown-linux-gnu]
+available = true
+
+[pkg.rust-analyzer-preview.target.x86_64-unknown-linux-gnu]
+available = true
+
+[renames.clippy]
+to = "clippy-preview"
+
+[renames.miri]
+to = "miri-preview"
+"#;
+        assert_eq!(
+            manifest_components(manifest, "x86_64-unknown-linux-gnu").unwrap(),
+            ["clippy", "rust-analyzer", "rust-src", "rustc"]
+        );
+        assert_eq!(
+            manifest_components(manifest, "aarch64-apple-darwin").unwrap(),
+            ["rust-src", "rustc", "rustc-dev"]
+        );
+    }
+
+    #[test]
+    fn test_ci_url_templates() {
+        let templates = |args: &[&str]| {
+            let args = Opts::try_parse_from(["cargo-bisect-rustc"].iter().chain(args)).unwrap();
+            ci_url_templates(&args)
+        };
+        assert_eq!(
+            templates(&[]),
+            ["https://ci-artifacts.rust-lang.org/rustc-builds/{commit}/{component}"]
+        );
+        assert_eq!(
+            templates(&["--alt", "--artifact-mirror=https://mirror.example.com/"]),
+            [
+                "https://mirror.example.com/rustc-builds-alt/{commit}/{component}",
+                "https://ci-artifacts.rust-lang.org/rustc-builds-alt/{commit}/{component}",
+            ]
+        );
+        assert_eq!(
+            templates(&[
+                "--artifact-mirror=https://mirror.example.com",
+                "--ci-url-template={server}/builds/{commit}/x86_64/{component}",
+            ]),
+            [
+                "https://mirror.example.com/builds/{commit}/x86_64/{component}",
+                "https://ci-artifacts.rust-lang.org/builds/{commit}/x86_64/{component}",
+            ]
+        );
+        assert_eq!(
+            templates(&[
+                "--artifact-mirror=https://mirror.example.com",
+                "--ci-url-template=https://example.com/{commit}/{component}",
+            ]),
+            ["https://example.com/{commit}/{component}"]
+        );
+        assert_eq!(
+            expand_url(
+                &templates(&[])[0],
+                "0123abcd",
+                "rustc-nightly-x86_64-unknown-linux-gnu"
+            ),
+            "https://ci-artifacts.rust-lang.org/rustc-builds/0123abcd/\
+             rustc-nightly-x86_64-unknown-linux-gnu"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_script() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = tempfile::tempdir().unwrap();
+        let test_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(base.path().join("scripts")).unwrap();
+        let path = base.path().join("scripts/test.sh");
+        fs::write(&path, "#!/bin/sh\ntest -f marker\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(test_dir.path().join("marker"), "").unwrap();
+
+        let script = resolve_script(base.path(), Path::new("scripts/test.sh"));
+        assert_eq!(script, path);
+        // The relative path still finds the script when run from the test
+        // directory, which is somewhere else entirely.
+        let status = Command::new(&script)
+            .current_dir(test_dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        // Scripts that are not files are left to be looked up in `PATH`.
+        assert_eq!(
+            resolve_script(base.path(), Path::new("true")),
+            Path::new("true")
+        );
+    }
+
+    #[test]
+    fn test_script_invocation() {
+        let invocation = |script: &str, shell, windows| {
+            script_invocation(Path::new(script), shell, windows)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(invocation("test.sh", None, false), ["test.sh"]);
+        assert_eq!(invocation("test.sh", None, true), ["bash", "test.sh"]);
+        assert_eq!(
+            invocation("test.ps1", None, true),
+            [
+                "powershell",
+                "-NoProfile",
+                "-ExecutionPolicy",
+                "Bypass",
+                "-File",
+                "test.ps1"
+            ]
+        );
+        assert_eq!(invocation("test.bat", None, true), ["test.bat"]);
+        assert_eq!(
+            invocation("test.sh", Some("zsh"), false),
+            ["zsh", "test.sh"]
+        );
+        assert_eq!(
+            invocation("test.ps1", Some("pwsh"), true),
+            ["pwsh", "test.ps1"]
+        );
+    }
+
+    #[test]
+    fn test_nightly_of_version() {
+        let meta = |verbose: &str| rustc_version::version_meta_for(verbose).unwrap();
+        let nightly = meta(
+            "rustc 1.70.0-nightly (9d0eac4d0 2023-03-17)
+binary: rustc
+commit-hash: 9d0eac4d0234c3f1ad2a1e5b8be0d8d0a1c1d56f
+commit-date: 2023-03-17
+host: x86_64-unknown-linux-gnu
+release: 1.70.0-nightly
+LLVM version: 15.0.7",
+        );
+        assert_eq!(
+            nightly_of_version(&nightly),
+            Some(NaiveDate::from_ymd_opt(2023, 3, 18).unwrap())
+        );
+
+        // Across the end of a month and of a year.
+        assert_eq!(
+            nightly_date_of_commit_date(NaiveDate::from_ymd_opt(2023, 2, 28).unwrap()),
+            NaiveDate::from_ymd_opt(2023, 3, 1).unwrap()
+        );
+        assert_eq!(
+            nightly_date_of_commit_date(NaiveDate::from_ymd_opt(2022, 12, 31).unwrap()),
+            NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()
+        );
+
+        let stable = meta(
+            "rustc 1.68.0 (2c8cc3432 2023-03-06)
+binary: rustc
+commit-hash: 2c8cc343237b8f7d5a3c3703e3a87f2eb2c54a74
+commit-date: 2023-03-06
+host: x86_64-unknown-linux-gnu
+release: 1.68.0
+LLVM version: 15.0.6",
+        );
+        assert_eq!(nightly_of_version(&stable), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bounded_output() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf 0123456789; printf abc >&2; exit 3"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let output = bounded_output(&mut cmd, 4).unwrap();
+        assert_eq!(output.stdout, b"0123");
+        assert_eq!(output.stderr, b"abc");
+        assert_eq!(output.status.code(), Some(3));
+    }
+
+    fn test_run(toolchain: &str, stdout: &str) -> TestRun {
+        TestRun {
+            toolchain: toolchain.to_string(),
+            output: process::Output {
+                status: process::ExitStatus::default(),
+                stdout: stdout.into(),
+                stderr: Vec::new(),
+            },
+            elapsed: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_first_runs_repeat() {
+        // With `--repeat`, the first two runs are of the start of the range.
+        let mut runs = FirstRuns::default();
+        assert!(runs.record(test_run("start", "ok")).is_none());
+        assert!(runs.record(test_run("start", "ok")).is_none());
+        let (first, second) = runs.record(test_run("end", "error")).unwrap();
+        assert_eq!(first.toolchain, "start");
+        assert_eq!(second.toolchain, "end");
+        assert!(!first.looks_like(&second));
+        // Only the first pair is compared.
+        assert!(runs.record(test_run("middle", "ok")).is_none());
+    }
+
+    #[test]
+    fn test_first_runs_retry() {
+        // A retry at a `--prompt` keeps the first run of the toolchain.
+        let mut runs = FirstRuns::default();
+        assert!(runs.record(test_run("start", "ok")).is_none());
+        assert!(runs.record(test_run("start", "flaky")).is_none());
+        let (first, second) = runs.record(test_run("end", "ok")).unwrap();
+        assert_eq!(first.toolchain, "start");
+        assert!(first.looks_like(&second));
+    }
+
+    #[test]
+    fn test_evict_target_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        for (i, name) in [
+            "target-bisector-ci-a",
+            "target-bisector-ci-b",
+            "target-bisector-ci-c",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let path = dir.path().join(name);
+            mark_target_dir_used(&path).unwrap();
+            let modified = now - Duration::from_secs(60 * (3 - i as u64));
+            fs::OpenOptions::new()
+                .write(true)
+                .open(path.join(TARGET_DIR_USED))
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::create_dir(dir.path().join("target-mine")).unwrap();
+
+        evict_target_dirs(dir.path(), 2).unwrap();
+        assert!(!dir.path().join("target-bisector-ci-a").exists());
+        assert!(dir.path().join("target-bisector-ci-b").exists());
+        assert!(dir.path().join("target-bisector-ci-c").exists());
+        // Only the directories of the tested toolchains are evicted.
+        assert!(dir.path().join("target").exists());
+        assert!(dir.path().join("target-mine").exists());
+    }
+
+    #[test]
+    fn test_copy_test_dir() {
+        let from = tempfile::tempdir().unwrap();
+        fs::create_dir_all(from.path().join("src")).unwrap();
+        fs::write(from.path().join("Cargo.toml"), "[package]").unwrap();
+        fs::write(from.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::create_dir_all(from.path().join("target/debug")).unwrap();
+        fs::create_dir_all(from.path().join("target-bisector-ci-abc/debug")).unwrap();
+        fs::create_dir_all(from.path().join("src/target")).unwrap();
+
+        let to = tempfile::tempdir().unwrap();
+        let to = to.path().join("copy");
+        copy_test_dir(from.path(), &to).unwrap();
+        assert!(to.join("Cargo.toml").is_file());
+        assert!(to.join("src/main.rs").is_file());
+        // Only the target directories at the top are build output.
+        assert!(to.join("src/target").is_dir());
+        assert!(!to.join("target").exists());
+        assert!(!to.join("target-bisector-ci-abc").exists());
+    }
+
+    #[test]
+    fn test_is_bisector_toolchain() {
+        assert!(is_bisector_toolchain(
+            "bisector-nightly-2023-01-01-x86_64-unknown-linux-gnu"
+        ));
+        assert!(is_bisector_toolchain(
+            "bisector-ci-0123456789abcdef-x86_64-unknown-linux-gnu"
+        ));
+        assert!(is_bisector_toolchain(
+            "bisector-local-x86_64-unknown-linux-gnu"
+        ));
+        assert!(!is_bisector_toolchain("nightly-x86_64-unknown-linux-gnu"));
+        assert!(!is_bisector_toolchain("stable-bisector-ci"));
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let (name, value) = parse_header("Authorization: Bearer abc:123").unwrap();
+        assert_eq!(name, "authorization");
+        assert_eq!(value, "Bearer abc:123");
+        assert!(value.is_sensitive());
+        assert!(parse_header("X-Token").is_err());
+        assert!(parse_header("bad name: value").is_err());
+        assert!(parse_header("X-Token: line\nbreak").is_err());
+    }
+
+    #[test]
+    fn test_network_allowed() {
+        assert!(network_allowed(false, "https://example.com").is_ok());
+        let err = network_allowed(true, "https://example.com").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "refusing to access https://example.com because of --no-network"
+        );
+    }
+
+    #[test]
+    fn test_retry_truncated() {
+        let truncated = || DownloadError::Truncated {
+            url: "https://example.com/rustc.tar.xz".to_string(),
+            received: 10,
+            expected: 20,
+        };
+        let mut attempts = 0;
+        let res = retry_truncated(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(truncated())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(res.is_ok());
+        assert_eq!(attempts, 2);
+
+        let mut attempts = 0;
+        let res = retry_truncated(|| {
+            attempts += 1;
+            Err(truncated())
+        });
+        assert!(matches!(res, Err(DownloadError::Truncated { .. })));
+        assert_eq!(attempts, 2);
+
+        let mut attempts = 0;
+        let res = retry_truncated(|| {
+            attempts += 1;
+            Err(DownloadError::NotFound("missing".to_string()))
+        });
+        assert!(matches!(res, Err(DownloadError::NotFound(_))));
+        assert_eq!(attempts, 1);
     }
 }
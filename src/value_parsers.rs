@@ -0,0 +1,316 @@
+//! Value parsers for the command-line options that take structured strings,
+//! so that a typo gets an error naming the expected form instead of a
+//! confusing failure later on.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+
+/// Checks that `s` names an existing directory.
+pub(crate) fn validate_dir(s: &str) -> anyhow::Result<PathBuf> {
+    let path: PathBuf = s.parse()?;
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        bail!(
+            "{} is not an existing directory",
+            path.canonicalize()?.display()
+        )
+    }
+}
+
+/// Splits `s` at the first `separator` into a non-empty key and a value, for
+/// options of the form `KEY=VALUE` or `KEY:VALUE`.
+pub(crate) fn split_key_value(s: &str, separator: char) -> Result<(&str, &str), String> {
+    let Some((key, value)) = s.split_once(separator) else {
+        return Err(format!("expected `KEY{separator}VALUE`, got `{s}`"));
+    };
+    if key.trim().is_empty() {
+        return Err(format!(
+            "expected `KEY{separator}VALUE`, got `{s}` with an empty key"
+        ));
+    }
+    Ok((key.trim(), value))
+}
+
+/// Checks that `s` looks like a target triple, such as
+/// `x86_64-unknown-linux-gnu`: at least two dash-separated components, made
+/// of letters, digits, `_` and `.`.
+pub(crate) fn validate_triple(s: &str) -> Result<String, String> {
+    let valid_component = |c: &str| {
+        !c.is_empty()
+            && c.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    };
+    if s.split('-').count() >= 2 && s.split('-').all(valid_component) {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "expected a target triple such as `x86_64-unknown-linux-gnu`, got `{s}`"
+        ))
+    }
+}
+
+/// Checks a `--target`, which is either a target triple, see
+/// [`validate_triple`], or the path of a custom target specification ending
+/// in `.json`, as cargo accepts.
+pub(crate) fn validate_target(s: &str) -> Result<String, String> {
+    if is_target_spec(s) {
+        return Ok(s.to_string());
+    }
+    validate_triple(s)
+}
+
+/// Whether the target `s` is the path of a custom target specification rather
+/// than a triple.
+pub(crate) fn is_target_spec(s: &str) -> bool {
+    Path::new(s)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Checks that `s` is a positive, finite number of seconds, for
+/// `--max-build-seconds`.
+pub(crate) fn validate_seconds(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(seconds),
+        _ => Err(format!(
+            "expected a positive number of seconds such as `30` or `2.5`, got `{s}`"
+        )),
+    }
+}
+
+/// The placeholders a `--ci-url-template` may use.
+const CI_URL_PLACEHOLDERS: &[&str] = &["{server}", "{alt}", "{commit}", "{component}"];
+
+/// Checks that a `--ci-url-template` uses only the known placeholders, and
+/// that it has the `{commit}` and `{component}` ones that tell the artifacts
+/// apart.
+pub(crate) fn validate_ci_url_template(s: &str) -> Result<String, String> {
+    let mut rest = s;
+    while let Some(start) = rest.find('{') {
+        let placeholder = match rest[start..].find('}') {
+            Some(end) => &rest[start..=start + end],
+            None => &rest[start..],
+        };
+        if !CI_URL_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!(
+                "unknown placeholder `{placeholder}`, expected one of {}",
+                CI_URL_PLACEHOLDERS.join(", ")
+            ));
+        }
+        rest = &rest[start + placeholder.len()..];
+    }
+    for required in ["{commit}", "{component}"] {
+        if !s.contains(required) {
+            return Err(format!("the template must contain {required}"));
+        }
+    }
+    Ok(s.to_string())
+}
+
+/// How a `--host` or `--target` compares to the target triples rustc knows.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TripleCheck {
+    Known,
+    /// The triple only differs from a known one in its vendor, which is
+    /// missing or wrong, as in `x86_64-windows-msvc`.
+    Corrected(String),
+    /// The triple is close to a known one, likely a typo.
+    NearMiss(String),
+    /// The triple is not close to any known one. It may be newer than the
+    /// rustc the list comes from.
+    Unknown,
+}
+
+/// Compares `triple` to the `known` target triples.
+pub(crate) fn check_known_triple(triple: &str, known: &[String]) -> TripleCheck {
+    if known.iter().any(|k| k == triple) {
+        return TripleCheck::Known;
+    }
+    let parts: Vec<&str> = triple.split('-').collect();
+    let same_but_vendor = |k: &&String| {
+        let k: Vec<&str> = k.split('-').collect();
+        k.len() >= 3
+            && k[0] == parts[0]
+            && ((k.len() == parts.len() + 1 && k[2..] == parts[1..])
+                || (k.len() == parts.len() && k[2..] == parts[2..]))
+    };
+    let corrections: Vec<&String> = known.iter().filter(same_but_vendor).collect();
+    // Among several vendors, the usual ones are meant unless stated otherwise.
+    let usual: Vec<&String> = corrections
+        .iter()
+        .copied()
+        .filter(|k| matches!(k.split('-').nth(1), Some("pc" | "unknown")))
+        .collect();
+    match (corrections.as_slice(), usual.as_slice()) {
+        ([correction], _) | (_, [correction]) => {
+            return TripleCheck::Corrected((*correction).clone());
+        }
+        _ => {}
+    }
+    known
+        .iter()
+        .map(|k| (edit_distance(triple, k), k))
+        .filter(|&(distance, _)| distance <= 3)
+        .min()
+        .map_or(TripleCheck::Unknown, |(_, k)| {
+            TripleCheck::NearMiss(k.clone())
+        })
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in bytes.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.bytes().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_dir() {
+        let current_dir = ".";
+        assert!(validate_dir(current_dir).is_ok());
+        let main = "src/main.rs";
+        assert!(
+            validate_dir(main).is_err(),
+            "{}",
+            validate_dir(main).unwrap_err()
+        )
+    }
+
+    #[test]
+    fn test_split_key_value() {
+        assert_eq!(split_key_value("FOO=bar", '='), Ok(("FOO", "bar")));
+        assert_eq!(split_key_value("FOO=a=b", '='), Ok(("FOO", "a=b")));
+        assert_eq!(split_key_value("FOO=", '='), Ok(("FOO", "")));
+        assert_eq!(
+            split_key_value("FOO", '='),
+            Err("expected `KEY=VALUE`, got `FOO`".to_string())
+        );
+        assert!(split_key_value("=bar", '=').is_err());
+        assert!(split_key_value("Authorization: token", ':').is_ok());
+    }
+
+    #[test]
+    fn test_validate_triple() {
+        for triple in [
+            "x86_64-unknown-linux-gnu",
+            "aarch64-apple-darwin",
+            "wasm32-unknown-unknown",
+            "thumbv7em-none-eabihf",
+            "avr-none",
+        ] {
+            assert_eq!(validate_triple(triple).as_deref(), Ok(triple));
+        }
+        for bad in [
+            "",
+            "x86_64",
+            "x86_64--linux",
+            "x86_64-unknown-linux-gnu ",
+            "nightly/x",
+        ] {
+            assert!(validate_triple(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_validate_target() {
+        for target in [
+            "x86_64-unknown-linux-gnu",
+            "./my-target.json",
+            "targets/thumbv7em-custom.json",
+        ] {
+            assert_eq!(validate_target(target).as_deref(), Ok(target));
+        }
+        assert!(validate_target("x86_64").is_err());
+        assert!(validate_target("./my-target.toml").is_err());
+    }
+
+    #[test]
+    fn test_validate_seconds() {
+        assert_eq!(validate_seconds("30"), Ok(30.0));
+        assert_eq!(validate_seconds("2.5"), Ok(2.5));
+        for seconds in ["0", "-1", "NaN", "inf", "", "ten"] {
+            assert!(validate_seconds(seconds).is_err(), "{seconds}");
+        }
+    }
+
+    #[test]
+    fn test_validate_ci_url_template() {
+        for template in [
+            "{server}/rustc-builds{alt}/{commit}/{component}",
+            "https://example.com/{commit}/x86_64/{component}",
+        ] {
+            assert_eq!(validate_ci_url_template(template).as_deref(), Ok(template));
+        }
+        assert_eq!(
+            validate_ci_url_template("{server}/{sha}/{component}"),
+            Err("unknown placeholder `{sha}`, expected one of \
+                 {server}, {alt}, {commit}, {component}"
+                .to_string())
+        );
+        assert!(validate_ci_url_template("{server}/{commit}/{component").is_err());
+        assert_eq!(
+            validate_ci_url_template("{server}/{commit}/rustc.tar"),
+            Err("the template must contain {component}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_known_triple() {
+        let known: Vec<String> = [
+            "x86_64-pc-windows-msvc",
+            "x86_64-uwp-windows-msvc",
+            "x86_64-pc-windows-gnu",
+            "x86_64-unknown-linux-gnu",
+            "x86_64-unknown-linux-musl",
+            "aarch64-apple-darwin",
+            "wasm32-unknown-unknown",
+        ]
+        .map(String::from)
+        .to_vec();
+        let check = |triple| check_known_triple(triple, &known);
+
+        assert_eq!(check("x86_64-unknown-linux-gnu"), TripleCheck::Known);
+        assert_eq!(
+            check("x86_64-windows-msvc"),
+            TripleCheck::Corrected("x86_64-pc-windows-msvc".to_string())
+        );
+        assert_eq!(
+            check("x86_64-unknown-windows-gnu"),
+            TripleCheck::Corrected("x86_64-pc-windows-gnu".to_string())
+        );
+        assert_eq!(
+            check("x86_64-linux-gnu"),
+            TripleCheck::Corrected("x86_64-unknown-linux-gnu".to_string())
+        );
+        assert_eq!(
+            check("x86-64-unknown-linux-gnu"),
+            TripleCheck::NearMiss("x86_64-unknown-linux-gnu".to_string())
+        );
+        assert_eq!(
+            check("aarch64-apple-darwn"),
+            TripleCheck::NearMiss("aarch64-apple-darwin".to_string())
+        );
+        assert_eq!(check("riscv64gc-unknown-linux-gnu"), TripleCheck::Unknown);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("darwin", "darwn"), 1);
+    }
+}